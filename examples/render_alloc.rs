@@ -0,0 +1,50 @@
+// Counts the heap allocations one frame of the board takes. The options are the
+// game's own, the headless run grows a long snake to draw, e.g.
+//
+//     cargo run --release --example render_alloc -- --grid-width 200 --grid-height 100 --autopilot --seed 1 --headless 20000
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use ratatui::{buffer::Buffer, layout::Rect, widgets::Widget};
+use snake::Mode;
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const FRAMES: usize = 100;
+
+fn main() -> std::io::Result<()> {
+    let (mut app, mode) = snake::configure()?;
+    let max_ticks = match mode {
+        Mode::Headless { max_ticks } => max_ticks,
+        _ => 0,
+    };
+    let outcome = app.run_headless(std::iter::empty(), max_ticks);
+    println!("snake length {} after {} ticks", outcome.length, outcome.ticks);
+
+    let area = Rect::new(0, 0, 220, 110);
+    let mut buf = Buffer::empty(area);
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    for _ in 0..FRAMES {
+        buf.reset();
+        (&app).render(area, &mut buf);
+    }
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+    println!("{} allocations per frame", allocations / FRAMES);
+    Ok(())
+}
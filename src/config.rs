@@ -0,0 +1,1029 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use crossterm::event::KeyCode;
+use ratatui::{style::{Color, Style}, symbols::border, text::Span};
+
+use crate::game::{App, DeathPenalty, Difficulty, Direction, EfficiencyGoal, GameState, ObstacleLayout, MAX_TICK_MS, MIN_TICK_MS};
+use crate::input::{parse_script, Action, KeyBindings};
+use crate::ghost;
+use crate::leaderboard;
+use crate::render::HudPosition;
+use crate::save;
+use crate::stats;
+
+// Glyphs, colors and borders, read from the config file when there is one
+#[derive(Debug, Clone)]
+pub(crate) struct Theme {
+    // One glyph for every heading, None points the head the way the snake is
+    // moving with `heads`
+    pub(crate) head: Option<char>,
+    // The head facing up, down, left and right
+    pub(crate) heads: [char; 4],
+    pub(crate) tail: char,
+    pub(crate) food: char,
+    pub(crate) bonus: char,
+    pub(crate) wall: char,
+    pub(crate) portal: char,
+    pub(crate) head_color: Color,
+    pub(crate) tail_color: Color,
+    // The tail fades from `tail_color` next to the head to this at its end
+    pub(crate) tail_end_color: Color,
+    pub(crate) food_color: Color,
+    pub(crate) bonus_color: Color,
+    pub(crate) wall_color: Color,
+    pub(crate) portal_color: Color,
+    pub(crate) player_two_color: Color,
+    pub(crate) border_color: Color,
+    // Behind the board, Reset keeps the terminal's own
+    pub(crate) background: Color,
+    // Every other empty cell with the checkerboard on, a shade off `background`
+    pub(crate) checker_color: Color,
+    pub(crate) border: BorderStyle,
+    pub(crate) popup_border: BorderStyle,
+}
+
+// The line sets a board or popup border can be drawn with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BorderStyle {
+    Plain,
+    Rounded,
+    Double,
+    Thick,
+}
+
+impl BorderStyle {
+    pub(crate) const ALL: [BorderStyle; 4] =
+        [BorderStyle::Plain, BorderStyle::Rounded, BorderStyle::Double, BorderStyle::Thick];
+
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            BorderStyle::Plain => "plain",
+            BorderStyle::Rounded => "rounded",
+            BorderStyle::Double => "double",
+            BorderStyle::Thick => "thick",
+        }
+    }
+
+    pub(crate) fn set(self) -> border::Set {
+        match self {
+            BorderStyle::Plain => border::PLAIN,
+            BorderStyle::Rounded => border::ROUNDED,
+            BorderStyle::Double => border::DOUBLE,
+            BorderStyle::Thick => border::THICK,
+        }
+    }
+
+    pub(crate) fn next(self) -> BorderStyle {
+        let index = BorderStyle::ALL.iter().position(|style| *style == self).unwrap_or(0);
+        BorderStyle::ALL[(index + 1) % BorderStyle::ALL.len()]
+    }
+}
+
+impl Theme {
+    pub(crate) fn border_style(&self) -> Style {
+        Style::default().fg(self.border_color)
+    }
+}
+
+// What the settings screen calls each of `theme_presets`
+pub(crate) const THEME_NAMES: [&str; 5] = ["Default", "Green", "High contrast", "Solarized", "Colorblind"];
+
+// The theme from the config file, classic red when there is none, followed by
+// the built-in color schemes `t` cycles through. Those only swap colors, the
+// glyphs and borders stay as the config set them
+pub(crate) fn theme_presets(base: &Theme) -> Vec<Theme> {
+    vec![
+        base.clone(),
+        // Green on black
+        Theme {
+            head_color: Color::LightGreen,
+            tail_color: Color::Green,
+            tail_end_color: Color::Rgb(0, 95, 0),
+            food_color: Color::Yellow,
+            bonus_color: Color::LightYellow,
+            wall_color: Color::DarkGray,
+            portal_color: Color::Cyan,
+            player_two_color: Color::LightMagenta,
+            border_color: Color::Green,
+            background: Color::Black,
+            checker_color: Color::Rgb(0, 30, 0),
+            ..base.clone()
+        },
+        // High contrast
+        Theme {
+            head_color: Color::Yellow,
+            tail_color: Color::White,
+            tail_end_color: Color::Gray,
+            food_color: Color::LightRed,
+            bonus_color: Color::LightCyan,
+            wall_color: Color::White,
+            portal_color: Color::LightMagenta,
+            player_two_color: Color::LightCyan,
+            border_color: Color::White,
+            background: Color::Black,
+            checker_color: Color::Rgb(48, 48, 48),
+            ..base.clone()
+        },
+        // Solarized
+        Theme {
+            head_color: Color::Rgb(181, 137, 0),
+            tail_color: Color::Rgb(133, 153, 0),
+            tail_end_color: Color::Rgb(88, 110, 117),
+            food_color: Color::Rgb(220, 50, 47),
+            bonus_color: Color::Rgb(203, 75, 22),
+            wall_color: Color::Rgb(147, 161, 161),
+            portal_color: Color::Rgb(108, 113, 196),
+            player_two_color: Color::Rgb(42, 161, 152),
+            border_color: Color::Rgb(38, 139, 210),
+            background: Color::Rgb(0, 43, 54),
+            checker_color: Color::Rgb(7, 54, 66),
+            ..base.clone()
+        },
+        // Colorblind: the Okabe-Ito colors, a blue snake with a white head
+        // against orange food, which stay apart with any kind of color
+        // blindness and in brightness alone
+        Theme {
+            head_color: Color::White,
+            tail_color: Color::Rgb(86, 180, 233),
+            tail_end_color: Color::Rgb(0, 114, 178),
+            food_color: Color::Rgb(230, 159, 0),
+            bonus_color: Color::Rgb(240, 228, 66),
+            wall_color: Color::Gray,
+            portal_color: Color::Rgb(204, 121, 167),
+            player_two_color: Color::Rgb(0, 158, 115),
+            border_color: Color::Gray,
+            background: Color::Black,
+            checker_color: Color::Rgb(38, 38, 38),
+            ..base.clone()
+        },
+    ]
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            head: None,
+            heads: ['▲', '▼', '◀', '▶'],
+            tail: '○',
+            food: '■',
+            bonus: '★',
+            wall: '█',
+            portal: '◎',
+            head_color: Color::Red,
+            tail_color: Color::Red,
+            tail_end_color: Color::Rgb(95, 0, 0),
+            food_color: Color::Red,
+            bonus_color: Color::Yellow,
+            wall_color: Color::Gray,
+            portal_color: Color::Magenta,
+            player_two_color: Color::LightBlue,
+            border_color: Color::Reset,
+            background: Color::Reset,
+            checker_color: Color::Rgb(38, 38, 38),
+            border: BorderStyle::Thick,
+            popup_border: BorderStyle::Rounded,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Mode {
+    Single,
+    MultiGrid,
+    Headless { max_ticks: usize },
+    // A networked game against one other player, waiting for them on the address
+    Host { address: String },
+    Connect { address: String },
+    // Plays a run from a `--log` file back, the last one unless `--run` picks another
+    Replay { path: PathBuf, run: Option<usize> },
+}
+
+const MIN_GRID_SIZE: u16 = 10;
+const MAX_GRID_SIZE: u16 = 250;
+pub(crate) const MAX_FOOD: usize = 20;
+const MAX_MARGIN: u16 = 10;
+const LOCK_ATTEMPTS: u32 = 50;
+const LOCK_RETRY: Duration = Duration::from_millis(10);
+
+// The config file's switches, which the settings screen writes back. Each is
+// left as the game has it when the file doesn't say
+#[derive(Debug, Clone, Default)]
+struct Settings {
+    tick_ms: Option<u64>,
+    animate_food: Option<bool>,
+    sound: Option<bool>,
+    wrap_walls: Option<bool>,
+    walls_kill: Option<bool>,
+    checkerboard: Option<bool>,
+    square_cells: Option<bool>,
+    shadow: Option<bool>,
+    relative_controls: Option<bool>,
+    difficulty: Option<Difficulty>,
+    // Which of `theme_presets` to start with, over the one `t` last picked
+    theme: Option<usize>,
+}
+
+impl Settings {
+    fn apply(&self, app: &mut App) {
+        app.start_tick_ms = self.tick_ms.or(app.start_tick_ms);
+        app.animate_food = self.animate_food.unwrap_or(app.animate_food);
+        app.sound_enabled = self.sound.unwrap_or(app.sound_enabled);
+        app.wrap_walls = self.wrap_walls.unwrap_or(app.wrap_walls);
+        app.walls_kill = self.walls_kill.unwrap_or(app.walls_kill);
+        app.checkerboard = self.checkerboard.unwrap_or(app.checkerboard);
+        app.square_cells = self.square_cells.unwrap_or(app.square_cells);
+        app.shadow = self.shadow.unwrap_or(app.shadow);
+        app.relative_controls = self.relative_controls.unwrap_or(app.relative_controls);
+        app.difficulty = self.difficulty.unwrap_or(app.difficulty);
+    }
+}
+
+// Builds the app from the saved high score and the config file first, so command
+// line flags can override it
+pub fn configure() -> io::Result<(App, Mode)> {
+    let mut app = App::default();
+    app.high_score = load_high_score();
+    let (theme, keys, settings) = load_config()?;
+    app.keys = keys;
+    settings.apply(&mut app);
+    // A bad value here only costs the setting, the game still starts
+    if let Ok(value) = std::env::var("SNAKE_TICK_MS") {
+        match parse_tick_ms(&value) {
+            Ok(tick_ms) => app.start_tick_ms = Some(tick_ms),
+            Err(message) => eprintln!("snake: ignoring SNAKE_TICK_MS, {message}"),
+        }
+    }
+    app.themes = theme_presets(&theme);
+    app.theme_index = settings.theme.unwrap_or_else(load_theme_index) % app.themes.len();
+    app.theme = app.themes[app.theme_index].clone();
+    app.stats = stats::load();
+    app.saved_game = save::has_saved_game();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    app.args = logged_args(&args);
+    let mode = parse_args(&mut app, args)?;
+    // `--difficulty hardcore` picks the other board
+    app.leaderboard = leaderboard::load(app.hardcore());
+    // Only now is the board size known
+    if app.ghost {
+        app.best_run = ghost::load(app.grid_width, app.grid_height);
+    }
+    Ok((app, mode))
+}
+
+// What the run log keeps of the command line to set the game up again on
+// replay: every option but the ones naming a file, an address or another
+// way to run the game
+fn logged_args(args: &[String]) -> Vec<String> {
+    let mut logged = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--log" | "--script" | "--host" | "--connect" | "--headless" | "--replay" | "--run" => {
+                args.next();
+            }
+            "--multi-grid" => {}
+            _ => logged.push(arg.clone()),
+        }
+    }
+    logged
+}
+
+pub(crate) fn parse_args(app: &mut App, args: Vec<String>) -> io::Result<Mode> {
+    let mut mode = Mode::Single;
+    let mut custom_grid = false;
+    let mut replay_run = None;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--autopilot" => app.autopilot = true,
+            "--bonus" => app.bonus_food = true,
+            "--chaos" => app.chaos = true,
+            "--combo" => app.combo = true,
+            "--clamp-walls" => app.walls_kill = false,
+            "--constant-speed" => app.constant_speed = true,
+            "--debug" => app.debug = true,
+            "--difficulty" => {
+                let value = args.next().unwrap_or_default();
+                app.difficulty = Difficulty::ALL
+                    .into_iter()
+                    .find(|difficulty| difficulty.label().eq_ignore_ascii_case(&value))
+                    .ok_or_else(|| invalid_arg(format!("--difficulty expects easy, medium, hard or hardcore, got '{value}'")))?;
+                // Picking the difficulty up front skips the menu
+                app.state = GameState::Playing;
+            }
+            "--drift-food" => {
+                let value = args.next().unwrap_or_default();
+                let millis: u64 = value
+                    .parse()
+                    .ok()
+                    .filter(|millis| *millis > 0)
+                    .ok_or_else(|| invalid_arg(format!("--drift-food expects milliseconds between moves, got '{value}'")))?;
+                app.food_drift = Some(Duration::from_millis(millis));
+            }
+            "--food" => {
+                let value = args.next().unwrap_or_default();
+                app.food_count = value
+                    .parse()
+                    .ok()
+                    .filter(|count| (1..=MAX_FOOD).contains(count))
+                    .ok_or_else(|| invalid_arg(format!("--food expects 1 to {MAX_FOOD}, got '{value}'")))?;
+            }
+            "--goal" => {
+                let value = args.next().unwrap_or_default();
+                app.efficiency_goal = Some(parse_goal(&value).ok_or_else(|| {
+                    invalid_arg(format!("--goal expects <score>:<max-length>, got '{value}'"))
+                })?);
+            }
+            "--grid-width" => {
+                app.grid_width = parse_grid_size("--grid-width", args.next())?;
+                custom_grid = true;
+            }
+            "--grid-height" => {
+                app.grid_height = parse_grid_size("--grid-height", args.next())?;
+                custom_grid = true;
+            }
+            "--headless" => {
+                let value = args.next().unwrap_or_default();
+                let max_ticks = value
+                    .parse()
+                    .map_err(|_| invalid_arg(format!("--headless expects a tick count, got '{value}'")))?;
+                mode = Mode::Headless { max_ticks };
+            }
+            "--hunger" => {
+                let value = args.next().unwrap_or_default();
+                app.hunger = Some(
+                    value
+                        .parse()
+                        .ok()
+                        .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+                        .ok_or_else(|| invalid_arg(format!("--hunger expects seconds, got '{value}'")))?,
+                );
+            }
+            "--time-attack" => {
+                let value = args.next().unwrap_or_default();
+                app.time_attack = Some(
+                    value
+                        .parse()
+                        .ok()
+                        .filter(|secs| *secs > 0.0)
+                        .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+                        .ok_or_else(|| invalid_arg(format!("--time-attack expects seconds, got '{value}'")))?,
+                );
+            }
+            "--ghost" => app.ghost = true,
+            "--log" => {
+                let path = args.next().ok_or_else(|| invalid_arg("--log expects a file path".to_string()))?;
+                app.log_path = Some(PathBuf::from(path));
+            }
+            "--forgiving-neck" => app.forgiving_neck = true,
+            "--heat-map" => app.heat_map = true,
+            "--tick-ms" => {
+                let value = args.next().unwrap_or_default();
+                app.start_tick_ms = Some(parse_tick_ms(&value).map_err(|message| invalid_arg(format!("--tick-ms {message}")))?);
+            }
+            "--checkerboard" => app.checkerboard = true,
+            "--square-cells" => app.square_cells = true,
+            "--shadow" => app.shadow = true,
+            "--rival" => app.rival = true,
+            "--survival" => app.survival = true,
+            "--poison" => app.poison = true,
+            "--free-camera" => app.free_camera = true,
+            "--hud" => {
+                let value = args.next().unwrap_or_default();
+                app.hud_position = match value.as_str() {
+                    "top" => HudPosition::Top,
+                    "bottom" => HudPosition::Bottom,
+                    "hidden" => HudPosition::Hidden,
+                    _ => return Err(invalid_arg(format!("--hud expects top, bottom or hidden, got '{value}'"))),
+                };
+            }
+            "--margin" => {
+                let value = args.next().unwrap_or_default();
+                app.margin = value
+                    .parse()
+                    .ok()
+                    .filter(|margin| *margin <= MAX_MARGIN)
+                    .ok_or_else(|| invalid_arg(format!("--margin expects 0 to {MAX_MARGIN}, got '{value}'")))?;
+            }
+            "--multi-grid" => mode = Mode::MultiGrid,
+            "--host" => {
+                let address = args.next().ok_or_else(|| invalid_arg("--host expects an address like 0.0.0.0:7777".to_string()))?;
+                mode = Mode::Host { address };
+            }
+            "--connect" => {
+                let address = args.next().ok_or_else(|| invalid_arg("--connect expects an address like 192.168.1.2:7777".to_string()))?;
+                mode = Mode::Connect { address };
+            }
+            "--replay" => {
+                let path = args.next().ok_or_else(|| invalid_arg("--replay expects the file a run was logged to".to_string()))?;
+                mode = Mode::Replay { path: PathBuf::from(path), run: None };
+            }
+            "--run" => {
+                let value = args.next().unwrap_or_default();
+                replay_run = Some(
+                    value
+                        .parse()
+                        .ok()
+                        .filter(|run| *run >= 1)
+                        .ok_or_else(|| invalid_arg(format!("--run expects a run number from 1, got '{value}'")))?,
+                );
+            }
+            "--obstacles" => {
+                let value = args.next().unwrap_or_default();
+                app.obstacle_layout = match value.as_str() {
+                    "none" => ObstacleLayout::None,
+                    "pillars" => ObstacleLayout::Pillars,
+                    "cross" => ObstacleLayout::Cross,
+                    "frame" => ObstacleLayout::Frame,
+                    _ => return Err(invalid_arg(format!("--obstacles expects none, pillars, cross or frame, got '{value}'"))),
+                };
+            }
+            "--start-length" => {
+                let value = args.next().unwrap_or_default();
+                app.start_length = Some(
+                    value
+                        .parse()
+                        .ok()
+                        .filter(|length| *length >= 1)
+                        .ok_or_else(|| invalid_arg(format!("--start-length expects a whole number from 1, got '{value}'")))?,
+                );
+            }
+            "--lives" => {
+                let value = args.next().unwrap_or_default();
+                app.lives = Some(
+                    value
+                        .parse()
+                        .ok()
+                        .filter(|lives| *lives >= 1)
+                        .ok_or_else(|| invalid_arg(format!("--lives expects a whole number from 1, got '{value}'")))?,
+                );
+            }
+            "--death-penalty" => {
+                let value = args.next().unwrap_or_default();
+                let penalty = match value.strip_suffix('%') {
+                    Some(percent) => percent.parse().ok().filter(|percent| *percent <= 100).map(DeathPenalty::Percent),
+                    None => value.parse().ok().map(DeathPenalty::Points),
+                };
+                app.death_penalty = Some(penalty.ok_or_else(|| {
+                    invalid_arg(format!("--death-penalty expects points or a percentage up to 100%, got '{value}'"))
+                })?);
+            }
+            "--growth" => {
+                let value = args.next().unwrap_or_default();
+                app.growth_per_food = value
+                    .parse()
+                    .ok()
+                    .filter(|growth| *growth >= 1)
+                    .ok_or_else(|| invalid_arg(format!("--growth expects a whole number of segments from 1, got '{value}'")))?;
+            }
+            "--start-direction" => {
+                let value = args.next().unwrap_or_default();
+                app.start_direction = Some(match value.as_str() {
+                    "up" => Direction::Up,
+                    "down" => Direction::Down,
+                    "left" => Direction::Left,
+                    "right" => Direction::Right,
+                    _ => return Err(invalid_arg(format!("--start-direction expects up, down, left or right, got '{value}'"))),
+                });
+            }
+            "--scoring" => {
+                let value = args.next().unwrap_or_default();
+                app.classic_scoring = match value.as_str() {
+                    "classic" => true,
+                    "scaled" => false,
+                    _ => return Err(invalid_arg(format!("--scoring expects classic or scaled, got '{value}'"))),
+                };
+            }
+            "--practice" => app.practice = true,
+            "--relative-controls" => app.relative_controls = true,
+            "--safe-food" => app.safe_food = true,
+            "--screen-shake" => app.screen_shake = true,
+            "--portals" => app.portals_enabled = true,
+            "--radar" => app.radar = true,
+            "--assist" => app.assist = true,
+            "--speed-colors" => app.speed_colors = true,
+            "--tongue" => app.tongue = true,
+            "--still-food" => app.animate_food = false,
+            "--two-player" => app.players = 2,
+            "--training" => app.training = true,
+            "--wrap" => app.wrap_walls = true,
+            "--zen" => {
+                app.zen = true;
+                app.walls_kill = false;
+            }
+            "--warmup" => {
+                let value = args.next().unwrap_or_default();
+                app.warmup_duration = value
+                    .parse()
+                    .ok()
+                    .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+                    .ok_or_else(|| invalid_arg(format!("--warmup expects seconds, got '{value}'")))?;
+            }
+            "--script" => {
+                let path = args.next().ok_or_else(|| invalid_arg("--script expects a file path".to_string()))?;
+                app.script = parse_script(&std::fs::read_to_string(path)?)?;
+            }
+            "--seed" => {
+                let value = args.next().unwrap_or_default();
+                let seed = value
+                    .parse()
+                    .map_err(|_| invalid_arg(format!("--seed expects a whole number, got '{value}'")))?;
+                app.rng = StdRng::seed_from_u64(seed);
+                app.seed = Some(seed);
+            }
+            other => return Err(invalid_arg(format!("unknown option '{other}'"))),
+        }
+    }
+
+    match (&mut mode, replay_run) {
+        (Mode::Replay { run, .. }, picked) => *run = picked,
+        (_, Some(_)) => return Err(invalid_arg("--run only goes with --replay".to_string())),
+        _ => {}
+    }
+
+    app.validate_start().map_err(|error| invalid_arg(error.to_string()))?;
+
+    // Headless runs never draw, so any grid will do, and the free camera
+    // scrolls one that doesn't fit
+    if custom_grid
+        && !matches!(mode, Mode::Headless { .. })
+        && !app.free_camera
+        && let Ok((columns, rows)) = crossterm::terminal::size()
+        && (app.grid_width > columns || app.grid_height > rows)
+    {
+        return Err(invalid_arg(format!(
+            "a {}x{} grid does not fit in this {columns}x{rows} terminal, --free-camera scrolls it",
+            app.grid_width, app.grid_height
+        )));
+    }
+    Ok(mode)
+}
+
+// A board side, border included, the game can play on. Saved games and
+// versus greetings come from outside too, so they go through this as well
+pub(crate) fn grid_size(value: &str) -> Option<u16> {
+    value.parse().ok().filter(|size| (MIN_GRID_SIZE..=MAX_GRID_SIZE).contains(size))
+}
+
+fn parse_grid_size(option: &str, value: Option<String>) -> io::Result<u16> {
+    let value = value.unwrap_or_default();
+    grid_size(&value)
+        .ok_or_else(|| {
+            invalid_arg(format!(
+                "{option} expects {MIN_GRID_SIZE} to {MAX_GRID_SIZE}, got '{value}'"
+            ))
+        })
+}
+
+fn parse_goal(value: &str) -> Option<EfficiencyGoal> {
+    let (score, length) = value.split_once(':')?;
+    Some(EfficiencyGoal {
+        target_score: score.parse().ok()?,
+        max_length: length.parse().ok()?,
+    })
+}
+
+// Saved games and scores live in the user's data directory, e.g. ~/.local/share/snake
+pub(crate) fn data_dir() -> Option<PathBuf> {
+    let base = if cfg!(windows) {
+        PathBuf::from(std::env::var_os("APPDATA")?)
+    } else {
+        match std::env::var_os("XDG_DATA_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(std::env::var_os("HOME")?).join(".local").join("share"),
+        }
+    };
+    Some(base.join("snake"))
+}
+
+// A missing or unreadable file just means there is no best score yet
+fn load_high_score() -> u32 {
+    data_dir()
+        .and_then(|dir| std::fs::read_to_string(dir.join("high_score")).ok())
+        .and_then(|contents| contents.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+pub(crate) fn save_high_score(score: u32) -> io::Result<()> {
+    let dir = data_dir().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no data directory"))?;
+    std::fs::create_dir_all(&dir)?;
+    write_high_score(&dir, score)
+}
+
+// Two games on the same machine can end at once, so the file is read again
+// under a lock and only ever raised, whichever of them writes last. A lock
+// still held after LOCK_ATTEMPTS tries was left by a game that crashed and
+// the write goes ahead without it
+fn write_high_score(dir: &Path, score: u32) -> io::Result<()> {
+    let lock = dir.join("high_score.lock");
+    for _ in 0..LOCK_ATTEMPTS {
+        match std::fs::OpenOptions::new().write(true).create_new(true).open(&lock) {
+            Ok(_) => break,
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => std::thread::sleep(LOCK_RETRY),
+            Err(err) => return Err(err),
+        }
+    }
+    let path = dir.join("high_score");
+    let saved = std::fs::read_to_string(&path).ok().and_then(|contents| contents.trim().parse().ok());
+    let written = if saved.is_none_or(|saved: u32| score > saved) {
+        let temporary = path.with_extension(std::process::id().to_string());
+        std::fs::write(&temporary, score.to_string()).and_then(|_| std::fs::rename(&temporary, &path))
+    } else {
+        Ok(())
+    };
+    let _ = std::fs::remove_file(&lock);
+    written
+}
+
+// The scheme `t` last picked, the config file's own theme when there is none
+fn load_theme_index() -> usize {
+    data_dir()
+        .and_then(|dir| std::fs::read_to_string(dir.join("theme")).ok())
+        .and_then(|contents| contents.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+pub(crate) fn save_theme_index(index: usize) -> io::Result<()> {
+    let dir = data_dir().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no data directory"))?;
+    std::fs::create_dir_all(&dir)?;
+    std::fs::write(dir.join("theme"), index.to_string())
+}
+
+fn config_dir() -> Option<PathBuf> {
+    let base = if cfg!(windows) {
+        PathBuf::from(std::env::var_os("APPDATA")?)
+    } else {
+        match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+        }
+    };
+    Some(base.join("snake"))
+}
+
+// No config file means the built-in look and keys, a broken one is reported so
+// a typo doesn't go unnoticed
+fn load_config() -> io::Result<(Theme, KeyBindings, Settings)> {
+    let Some(path) = config_path() else {
+        return Ok((Theme::default(), KeyBindings::default(), Settings::default()));
+    };
+    match std::fs::read_to_string(&path) {
+        Ok(contents) => parse_config(&contents)
+            .map_err(|message| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {message}", path.display()))),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            Ok((Theme::default(), KeyBindings::default(), Settings::default()))
+        }
+        Err(err) => Err(err),
+    }
+}
+
+fn config_path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("config.toml"))
+}
+
+// The keys the settings screen writes, quoted like TOML wants strings
+const SETTING_KEYS: [&str; 9] = [
+    "sound",
+    "wrap_walls",
+    "walls_kill",
+    "checkerboard",
+    "square_cells",
+    "shadow",
+    "relative_controls",
+    "difficulty",
+    "theme",
+];
+
+pub(crate) fn setting_values(app: &App) -> [String; 9] {
+    [
+        app.sound_enabled.to_string(),
+        app.wrap_walls.to_string(),
+        app.walls_kill.to_string(),
+        app.checkerboard.to_string(),
+        app.square_cells.to_string(),
+        app.shadow.to_string(),
+        app.relative_controls.to_string(),
+        format!("\"{}\"", app.difficulty.label().to_lowercase()),
+        format!("\"{}\"", THEME_NAMES[app.theme_index].to_lowercase()),
+    ]
+}
+
+// Rewrites the settings screen's lines in the config file and keeps everything
+// else in it as it was: a setting already there is replaced in place, a new one
+// goes before the first section so it isn't read as part of `[keys]`
+pub(crate) fn save_settings(values: &[String; 9]) -> io::Result<()> {
+    let path = config_path().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no config directory"))?;
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err),
+    };
+    let mut written = [false; SETTING_KEYS.len()];
+    let mut in_keys = false;
+    let mut lines = Vec::new();
+    for line in contents.lines() {
+        let trimmed = line.trim();
+        if let Some(section) = trimmed.strip_prefix('[') {
+            in_keys = section.split(']').next().is_some_and(|name| name.trim() == "keys");
+        }
+        let setting = trimmed
+            .split_once('=')
+            .filter(|_| !in_keys && !trimmed.starts_with('#'))
+            .and_then(|(key, _)| SETTING_KEYS.iter().position(|known| *known == key.trim()));
+        match setting {
+            Some(index) => {
+                lines.push(format!("{} = {}", SETTING_KEYS[index], values[index]));
+                written[index] = true;
+            }
+            None => lines.push(line.to_string()),
+        }
+    }
+    let missing = SETTING_KEYS
+        .iter()
+        .zip(values)
+        .zip(written)
+        .filter(|(_, written)| !written)
+        .map(|((key, value), _)| format!("{key} = {value}"));
+    let first_section = lines.iter().position(|line| line.trim().starts_with('[')).unwrap_or(lines.len());
+    lines.splice(first_section..first_section, missing);
+    let dir = path.parent().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no config directory"))?;
+    std::fs::create_dir_all(dir)?;
+    // Written aside and renamed over the old file, so a crash halfway leaves it whole
+    let temp = dir.join(format!("config.toml.{}", std::process::id()));
+    std::fs::write(&temp, lines.join("\n") + "\n")?;
+    std::fs::rename(&temp, &path)
+}
+
+// The small subset of TOML the config needs: `key = value` lines, `#` comments
+// and `[section]` headers. Lines under `[keys]` remap keys, `tick_ms` and the
+// settings screen's switches set how the game starts and every other line is
+// part of the theme whatever section it is in
+fn parse_config(contents: &str) -> Result<(Theme, KeyBindings, Settings), String> {
+    let mut theme = Theme::default();
+    let mut keys = KeyBindings::default();
+    let mut settings = Settings::default();
+    let mut in_keys = false;
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(section) = line.strip_prefix('[') {
+            in_keys = section.split(']').next().is_some_and(|name| name.trim() == "keys");
+            continue;
+        }
+        let line_error = |message: String| format!("line {}: {message}", index + 1);
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| line_error(format!("expected `key = value`, got '{line}'")))?;
+        let key = key.trim();
+        if in_keys {
+            let action = Action::ALL
+                .into_iter()
+                .find(|action| action.name() == key)
+                .ok_or_else(|| line_error(format!("unknown action '{key}'")))?;
+            let names = parse_toml_strings(value)
+                .ok_or_else(|| line_error(format!("{key} expects a quoted key name or a list of them")))?;
+            let codes = names.iter().map(|name| parse_key(name)).collect::<Result<_, _>>().map_err(line_error)?;
+            keys.bind(action, codes);
+            continue;
+        }
+        // Numbers and switches are written bare like TOML wants them
+        let bare = value.split('#').next().unwrap_or_default().trim();
+        if key == "tick_ms" {
+            settings.tick_ms = Some(parse_tick_ms(bare).map_err(|message| line_error(format!("tick_ms {message}")))?);
+            continue;
+        }
+        let switch = match key {
+            "animate_food" => Some(&mut settings.animate_food),
+            "sound" => Some(&mut settings.sound),
+            "wrap_walls" => Some(&mut settings.wrap_walls),
+            "walls_kill" => Some(&mut settings.walls_kill),
+            "checkerboard" => Some(&mut settings.checkerboard),
+            "square_cells" => Some(&mut settings.square_cells),
+            "shadow" => Some(&mut settings.shadow),
+            "relative_controls" => Some(&mut settings.relative_controls),
+            _ => None,
+        };
+        if let Some(switch) = switch {
+            *switch = Some(bare.parse().map_err(|_| line_error(format!("{key} expects true or false, got '{bare}'")))?);
+            continue;
+        }
+        let value = parse_toml_string(value).ok_or_else(|| line_error(format!("{key} expects a quoted string")))?;
+        // A snake or food glyph that doesn't fit a cell keeps the default
+        // rather than refusing the whole file
+        let skip = |message: String| eprintln!("snake: ignoring {key} on {}", line_error(message));
+        match key {
+            "difficulty" => {
+                settings.difficulty = Some(
+                    Difficulty::ALL
+                        .into_iter()
+                        .find(|difficulty| difficulty.label().eq_ignore_ascii_case(&value))
+                        .ok_or_else(|| line_error(format!("difficulty expects easy, medium, hard or hardcore, got '{value}'")))?,
+                )
+            }
+            "theme" => {
+                settings.theme = Some(
+                    THEME_NAMES
+                        .iter()
+                        .position(|name| name.eq_ignore_ascii_case(&value))
+                        .ok_or_else(|| {
+                            line_error(format!(
+                                "theme expects default, green, high contrast, solarized or colorblind, got '{value}'"
+                            ))
+                        })?,
+                )
+            }
+            "head" => match parse_symbol(&value) {
+                Ok(glyph) => theme.head = Some(glyph),
+                Err(message) => skip(message),
+            },
+            "head_up" | "head_down" | "head_left" | "head_right" => {
+                let index = ["head_up", "head_down", "head_left", "head_right"].iter().position(|name| *name == key);
+                match (index, parse_symbol(&value)) {
+                    (Some(index), Ok(glyph)) => theme.heads[index] = glyph,
+                    (_, Err(message)) => skip(message),
+                    (None, _) => {}
+                }
+            }
+            "tail" => match parse_symbol(&value) {
+                Ok(glyph) => theme.tail = glyph,
+                Err(message) => skip(message),
+            },
+            "food" => match parse_symbol(&value) {
+                Ok(glyph) => theme.food = glyph,
+                Err(message) => skip(message),
+            },
+            "bonus" => theme.bonus = parse_glyph(&value).map_err(line_error)?,
+            "wall" => theme.wall = parse_glyph(&value).map_err(line_error)?,
+            "portal" => theme.portal = parse_glyph(&value).map_err(line_error)?,
+            "head_color" => theme.head_color = parse_color(&value).map_err(line_error)?,
+            "tail_color" => theme.tail_color = parse_color(&value).map_err(line_error)?,
+            "tail_end_color" => theme.tail_end_color = parse_color(&value).map_err(line_error)?,
+            "food_color" => theme.food_color = parse_color(&value).map_err(line_error)?,
+            "bonus_color" => theme.bonus_color = parse_color(&value).map_err(line_error)?,
+            "wall_color" => theme.wall_color = parse_color(&value).map_err(line_error)?,
+            "portal_color" => theme.portal_color = parse_color(&value).map_err(line_error)?,
+            "player_two_color" => theme.player_two_color = parse_color(&value).map_err(line_error)?,
+            "border_color" => theme.border_color = parse_color(&value).map_err(line_error)?,
+            "background" => theme.background = parse_color(&value).map_err(line_error)?,
+            "checker_color" => theme.checker_color = parse_color(&value).map_err(line_error)?,
+            "border" => theme.border = parse_border(&value).map_err(line_error)?,
+            "popup_border" => theme.popup_border = parse_border(&value).map_err(line_error)?,
+            _ => return Err(line_error(format!("unknown key '{key}'"))),
+        }
+    }
+    if let Some((key, first, second)) = keys.conflict() {
+        return Err(format!(
+            "{} is bound to both {} and {}",
+            key_label(key),
+            first.name(),
+            second.name()
+        ));
+    }
+    Ok((theme, keys, settings))
+}
+
+fn parse_tick_ms(value: &str) -> Result<u64, String> {
+    value
+        .trim()
+        .parse()
+        .ok()
+        .filter(|tick_ms| (MIN_TICK_MS..=MAX_TICK_MS).contains(tick_ms))
+        .ok_or_else(|| format!("expects milliseconds from {MIN_TICK_MS} to {MAX_TICK_MS}, got '{value}'"))
+}
+
+fn parse_toml_string(value: &str) -> Option<String> {
+    // Anything after the closing quote may only be a comment
+    let rest = value.trim().strip_prefix('"')?;
+    let (inner, after) = rest.split_once('"')?;
+    let after = after.trim();
+    (after.is_empty() || after.starts_with('#')).then(|| inner.to_string())
+}
+
+// A single quoted string or a `[...]` list of them
+fn parse_toml_strings(value: &str) -> Option<Vec<String>> {
+    let Some(mut rest) = value.trim().strip_prefix('[') else {
+        return parse_toml_string(value).map(|single| vec![single]);
+    };
+    let mut values = Vec::new();
+    loop {
+        rest = rest.trim_start();
+        if let Some(after) = rest.strip_prefix(']') {
+            let after = after.trim();
+            return (after.is_empty() || after.starts_with('#')).then_some(values);
+        }
+        let (inner, after) = rest.strip_prefix('"')?.split_once('"')?;
+        values.push(inner.to_string());
+        rest = after.trim_start();
+        rest = rest.strip_prefix(',').unwrap_or(rest);
+    }
+}
+
+// Single characters stand for themselves, other keys go by name
+fn parse_key(name: &str) -> Result<KeyCode, String> {
+    let mut chars = name.chars();
+    if let (Some(character), None) = (chars.next(), chars.next()) {
+        return Ok(KeyCode::Char(character));
+    }
+    let lower = name.to_ascii_lowercase();
+    let code = match lower.as_str() {
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "space" => KeyCode::Char(' '),
+        "enter" => KeyCode::Enter,
+        "esc" => return Err("Esc always goes back and can't be bound".to_string()),
+        "tab" => KeyCode::Tab,
+        "backspace" => KeyCode::Backspace,
+        _ => match lower.strip_prefix('f').and_then(|number| number.parse().ok()) {
+            Some(number @ 1..=12) => KeyCode::F(number),
+            _ => {
+                return Err(format!(
+                    "'{name}' is not a key, use a single character or a name like \"Up\", \"Space\" or \"F5\""
+                ));
+            }
+        },
+    };
+    Ok(code)
+}
+
+fn key_label(code: KeyCode) -> String {
+    match code {
+        KeyCode::Char(' ') => "Space".to_string(),
+        KeyCode::Char(character) => format!("'{character}'"),
+        KeyCode::F(number) => format!("F{number}"),
+        other => format!("{other:?}"),
+    }
+}
+
+fn parse_glyph(value: &str) -> Result<char, String> {
+    let mut chars = value.chars();
+    match (chars.next(), chars.next()) {
+        (Some(glyph), None) => Ok(glyph),
+        _ => Err(format!("expected a single character, got '{value}'")),
+    }
+}
+
+// A glyph one cell wide, which the snake and its food have to be to line up
+fn parse_symbol(value: &str) -> Result<char, String> {
+    let glyph = parse_glyph(value)?;
+    if Span::raw(value).width() != 1 {
+        return Err(format!("expected a character one cell wide, got '{value}'"));
+    }
+    Ok(glyph)
+}
+
+fn parse_color(value: &str) -> Result<Color, String> {
+    value
+        .parse()
+        .map_err(|_| format!("'{value}' is not a color, use a name like \"red\" or \"#rrggbb\""))
+}
+
+fn parse_border(value: &str) -> Result<BorderStyle, String> {
+    BorderStyle::ALL
+        .into_iter()
+        .find(|style| style.label().eq_ignore_ascii_case(value))
+        .ok_or_else(|| format!("border expects plain, rounded, double or thick, got '{value}'"))
+}
+
+fn invalid_arg(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_higher_of_two_games_writing_at_once_is_kept() {
+        let dir = std::env::temp_dir().join(format!("snake-high-score-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let writers: Vec<_> = (1..=8)
+            .map(|score| {
+                let dir = dir.clone();
+                std::thread::spawn(move || write_high_score(&dir, score * 10))
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap().unwrap();
+        }
+        write_high_score(&dir, 30).unwrap();
+        let saved = std::fs::read_to_string(dir.join("high_score")).unwrap();
+        assert!(!dir.join("high_score.lock").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(saved, "80");
+    }
+
+    #[test]
+    fn glyphs_that_dont_fit_a_cell_keep_the_default() {
+        let contents = "head_up = \"^\"\nhead_down = \"v\"\nhead_left = \"<<\"\nhead_right = \"🐍\"\nfood = \"\"\ntail = \"o\"\n";
+        let (theme, _, _) = parse_config(contents).unwrap();
+        assert_eq!(theme.heads, ['^', 'v', '◀', '▶']);
+        assert_eq!((theme.food, theme.tail), (Theme::default().food, 'o'));
+    }
+}
@@ -0,0 +1,3364 @@
+use std::cell::RefCell;
+use std::collections::{HashSet, VecDeque};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use ratatui::DefaultTerminal;
+
+use crate::config::{setting_values, theme_presets, Theme, THEME_NAMES};
+use crate::input::{KeyBindings, ScriptCommand, ScriptEvent};
+use crate::ghost::{self, Ghost, GhostStep};
+use crate::history;
+use crate::leaderboard::{self, ScoreEntry};
+use crate::render::{copy_to_clipboard, draw_frame, HudPosition};
+use crate::replay::{ReplayInput, ReplayStep};
+use crate::shutdown::{self, Store};
+use crate::stats::{self, Stats};
+
+#[derive(Debug, Clone)]
+#[derive(PartialEq, Eq, Hash)]
+pub struct Dot {
+    pub x: u16,
+    pub y: u16,
+}
+
+// One player's snake: the head, the body trailing it and the turns it still has to make
+#[derive(Debug, Clone)]
+pub(crate) struct Snake {
+    pub(crate) dot: Dot,
+    pub(crate) tail: VecDeque<Dot>,
+    pub(crate) tail_length: u16,
+    pub(crate) direction: Direction,
+    queued_turns: VecDeque<Direction>,
+    pub(crate) score: u32,
+    pub(crate) alive: bool,
+    // Food eaten in a row with `--combo`, each within COMBO_WINDOW ticks of the
+    // last, and the last tick the next one still counts
+    pub(crate) combo: u32,
+    combo_until: u64,
+}
+
+impl Snake {
+    pub(crate) fn new(dot: Dot, tail_length: u16) -> Self {
+        Self {
+            dot,
+            tail: VecDeque::new(),
+            tail_length,
+            direction: Direction::Up,
+            queued_turns: VecDeque::new(),
+            score: 0,
+            alive: true,
+            combo: 0,
+            combo_until: 0,
+        }
+    }
+
+    // A snake already at full length, its body laid out straight behind the
+    // head the way it is heading
+    pub(crate) fn laid_out(dot: Dot, tail_length: u16, direction: Direction) -> Self {
+        let mut snake = Self::new(dot, tail_length);
+        snake.direction = direction;
+        let (x, y) = (snake.dot.x, snake.dot.y);
+        snake.tail = (1..=tail_length)
+            .map(|step| match direction {
+                Direction::Up => Dot { x, y: y + step },
+                Direction::Down => Dot { x, y: y.saturating_sub(step) },
+                Direction::Left => Dot { x: x + step, y },
+                Direction::Right => Dot { x: x.saturating_sub(step), y },
+            })
+            .collect();
+        snake
+    }
+
+    // Cells the snake covers right now; lags behind `tail_length` while the tail
+    // is still growing out at the start
+    pub(crate) fn length(&self) -> usize {
+        self.tail.len() + 1
+    }
+
+    // Points for food eaten on `tick`: the combo multiplier after this eat
+    fn eat_combo(&mut self, tick: u64) -> u32 {
+        self.combo = if self.combo > 0 && tick <= self.combo_until {
+            (self.combo + 1).min(MAX_COMBO)
+        } else {
+            1
+        };
+        self.combo_until = tick + COMBO_WINDOW;
+        self.combo
+    }
+
+    // Turns that skip the queue, the autopilot's and a networked peer's, still
+    // can't reverse the snake
+    fn turn(&mut self, direction: Direction) {
+        if direction != self.direction.opposite() {
+            self.direction = direction;
+        }
+    }
+
+    // Turning rules: pressing the heading changes nothing and takes no place in
+    // line, a perpendicular direction is queued and the opposite direction is
+    // rejected so the snake can never reverse into its own neck. Keys arrive
+    // faster than ticks, so turns wait in line and each tick takes one. Every
+    // turn is checked against the one queued before it, which is the heading
+    // the snake will have by then, so two quick presses can't add up to a
+    // reversal
+    pub(crate) fn queue_turn(&mut self, direction: Direction) {
+        let heading = self.queued_turns.back().copied().unwrap_or(self.direction);
+        if direction != heading && direction != heading.opposite() && self.queued_turns.len() < MAX_QUEUED_TURNS {
+            self.queued_turns.push_back(direction);
+        }
+    }
+
+    // Where a turn to the snake's own left or right points, counted from the
+    // heading it will have once the turns already queued are made
+    pub(crate) fn rotated(&self, clockwise: bool) -> Direction {
+        let heading = self.queued_turns.back().copied().unwrap_or(self.direction);
+        if clockwise { heading.rotate_cw() } else { heading.rotate_ccw() }
+    }
+
+    // The turn the next tick will make, if any
+    pub(crate) fn next_turn(&self) -> Option<Direction> {
+        self.queued_turns.front().copied()
+    }
+
+    // Replaces the queue with a turn decided elsewhere, a networked opponent's,
+    // so the next tick makes exactly the turn it made on the other end
+    pub(crate) fn set_next_turn(&mut self, direction: Option<Direction>) {
+        self.queued_turns = direction.into_iter().collect();
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Food {
+    pub(crate) x: u16,
+    pub(crate) y: u16,
+    pub(crate) kind: FoodKind,
+}
+
+// `--survival` leaves a wall behind every plain bite, now and then the food
+// that turns up clears walls instead
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum FoodKind {
+    Plain,
+    // Takes every wall within CLEAR_RADIUS of it off the board when eaten
+    Clear,
+    // `--poison` in hardcore: cuts the tail in half instead of feeding it
+    Poison,
+}
+
+// Worth more than normal food but only sticks around for BONUS_LIFETIME
+#[derive(Debug, Clone)]
+pub(crate) struct BonusFood {
+    pub(crate) x: u16,
+    pub(crate) y: u16,
+    spawned_at: Instant,
+}
+
+// What the last piece of food eaten was worth and where it was, shown
+// floating off the board for a moment
+#[derive(Debug, Clone)]
+pub(crate) struct PointsGained {
+    pub(crate) x: u16,
+    pub(crate) y: u16,
+    pub(crate) points: u32,
+    pub(crate) at: Instant,
+}
+
+// What the head ran into: the segment, obstacle or other head on `cell`, or
+// the border just past `cell` in the direction `wall` when it left the board
+#[derive(Debug, Clone)]
+pub(crate) struct Crash {
+    pub(crate) cell: Dot,
+    pub(crate) wall: Option<Direction>,
+}
+
+// The board as it was before one tick, what `u` in practice brings back
+#[derive(Debug, Clone)]
+struct Snapshot {
+    snakes: Vec<Snake>,
+    occupancy: Vec<u16>,
+    food: Vec<Food>,
+    bonus: Option<BonusFood>,
+    obstacles: Vec<Dot>,
+    severed: Vec<(Dot, u64)>,
+    tick: u64,
+    tick_ms: u64,
+    rng: StdRng,
+    wall_rng: StdRng,
+}
+
+// Win by reaching `target_score` while the snake is at most `max_length` long
+#[derive(Debug, Clone)]
+pub(crate) struct EfficiencyGoal {
+    pub(crate) target_score: u32,
+    pub(crate) max_length: u16,
+}
+
+// `--chaos`: two rules rolled for each game from its seed, so a shared seed
+// plays with the same ones
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct Modifiers {
+    pub(crate) wrap: bool,
+    // Which quarter of the board gets a block of wall, counted row by row
+    pub(crate) cluster: Option<u16>,
+    // A fifth off the starting tick
+    pub(crate) faster: bool,
+    // Bonus food turns up after every other bite on average
+    pub(crate) frequent_bonus: bool,
+}
+
+impl Modifiers {
+    // For the title, in the order they are rolled
+    pub(crate) fn labels(&self) -> Vec<&'static str> {
+        [
+            (self.wrap, "wrap"),
+            (self.cluster.is_some(), "walls"),
+            (self.faster, "fast"),
+            (self.frequent_bonus, "bonus"),
+        ]
+        .into_iter()
+        .filter_map(|(on, label)| on.then_some(label))
+        .collect()
+    }
+}
+
+// What draws random numbers besides the food, each from a generator of its
+// own so drawing more or less in one leaves the rest where they were
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Stream {
+    Modifiers,
+    Walls,
+}
+
+impl Stream {
+    fn name(self) -> &'static str {
+        match self {
+            Stream::Modifiers => "modifiers",
+            Stream::Walls => "walls",
+        }
+    }
+}
+
+// A generator for `stream` off a game's seed: FNV-1a over the stream's name
+// and the seed's bytes. The food keeps `seed_from_u64(seed)` itself, which is
+// what every run logged before the streams were split was played with
+fn stream(seed: u64, stream: Stream) -> StdRng {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in stream.name().bytes().chain(seed.to_le_bytes()) {
+        hash = (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3);
+    }
+    StdRng::seed_from_u64(hash)
+}
+
+// Picks two of the four modifiers, and the quarter for the walls when they
+// are one of them
+pub(crate) fn roll_modifiers(rng: &mut impl Rng) -> Modifiers {
+    let mut modifiers = Modifiers::default();
+    for picked in rand::seq::index::sample(rng, 4, 2) {
+        match picked {
+            0 => modifiers.wrap = true,
+            1 => modifiers.cluster = Some(0),
+            2 => modifiers.faster = true,
+            _ => modifiers.frequent_bonus = true,
+        }
+    }
+    if modifiers.cluster.is_some() {
+        modifiers.cluster = Some(rng.gen_range(0..4));
+    }
+    modifiers
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Direction {
+    fn opposite(self) -> Direction {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+        }
+    }
+
+    // A quarter turn to the right of this heading as the snake sees it
+    pub(crate) fn rotate_cw(self) -> Direction {
+        match self {
+            Direction::Up => Direction::Right,
+            Direction::Right => Direction::Down,
+            Direction::Down => Direction::Left,
+            Direction::Left => Direction::Up,
+        }
+    }
+
+    pub(crate) fn rotate_ccw(self) -> Direction {
+        self.rotate_cw().opposite()
+    }
+}
+
+// How a headless run finished
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GameEnd {
+    Died,
+    Won,
+    // Ran out of ticks or inputs told it to quit while the snake was still alive
+    Stopped,
+}
+
+// Something that happened on a tick, for anything that wants to react to the
+// game without reaching into it: sound, logs, another player over the network
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GameEvent {
+    // A snake ate, `score` is its score with the food counted
+    AteFood { score: u32 },
+    // A snake's body got a segment longer, `length` counts the head
+    Grew { length: usize },
+    // A crash cost one of `--lives` and the snake is back at the start
+    LostLife { lives_left: u32 },
+    Died,
+    Won,
+}
+
+// `--death-penalty`: points off the score for every life lost but the last
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum DeathPenalty {
+    Points(u32),
+    Percent(u32),
+}
+
+impl DeathPenalty {
+    // Never more than the score, it stops at zero
+    pub(crate) fn of(&self, score: u32) -> u32 {
+        match self {
+            DeathPenalty::Points(points) => (*points).min(score),
+            DeathPenalty::Percent(percent) => score * percent / 100,
+        }
+    }
+}
+
+// What ended a run that was lost
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum DeathCause {
+    // The border, or an obstacle inside it
+    Wall,
+    // A body, the snake's own or the other player's, or the other head
+    SelfCollision,
+    // Hunger took the last segment
+    Starved,
+    // `--time-attack` ran out
+    TimeUp,
+}
+
+impl DeathCause {
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            DeathCause::Wall => "wall",
+            DeathCause::SelfCollision => "self_collision",
+            DeathCause::Starved => "starved",
+            DeathCause::TimeUp => "time_up",
+        }
+    }
+}
+
+// Why the options asked for a start that can't be played
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum StartError {
+    // The body laid out behind the head leaves no cell ahead to move into
+    TooLong { length: u16, room: u16, cells: u16, side: &'static str },
+    // Two snakes laid out side to side run into each other
+    SnakesCross,
+    // A body laid out over a portal
+    OnPortal,
+    // A body laid out on the frame, or a head about to run into it
+    OnFrame,
+    // The snake and the food don't both fit on the board
+    NoRoomForFood { food: usize, free: usize },
+}
+
+impl std::fmt::Display for StartError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            StartError::TooLong { length, room, cells, side } => write!(
+                f,
+                "--start-length expects at most {room} on a board {cells} cells {side}, got {length}"
+            ),
+            StartError::SnakesCross => write!(f, "two snakes starting left or right run into each other"),
+            StartError::OnPortal => write!(f, "the snake starts on a portal, shorten it or turn it"),
+            StartError::OnFrame => write!(f, "the snake starts on the frame, shorten it or make the board bigger"),
+            StartError::NoRoomForFood { food, free } => {
+                write!(f, "{food} food doesn't fit in the {free} cells the snake leaves free")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct GameOutcome {
+    pub score: u32,
+    pub length: usize,
+    pub ticks: u64,
+    pub end: GameEnd,
+}
+
+// Built-in interior wall layouts, scaled to the board
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ObstacleLayout {
+    None,
+    Pillars,
+    Cross,
+    // A ring of wall just inside the border, an arena the snake dies on the
+    // edge of even with `--clamp-walls`
+    Frame,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum GameState {
+    // The controls legend shown on start, Enter goes on to the menu or, when
+    // the difficulty was picked on the command line, straight into a game
+    Splash { skip_menu: bool },
+    Menu,
+    // The settings screen, reached from the menu
+    Settings,
+    // `b` on the menu: the board the seed `game_seed` lays out, held until
+    // Enter plays it or the arrows lay out the next seed's
+    Browse,
+    // Board is shown but frozen until `until`
+    Countdown { until: Instant },
+    Playing,
+    GameOver,
+    Won,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
+    // Wrapping walls, food that wanders off, a tail that starves and a snake
+    // that speeds up with every bite, scored on a leaderboard of its own
+    Hardcore,
+}
+
+impl Difficulty {
+    pub(crate) const ALL: [Difficulty; 4] = [Difficulty::Easy, Difficulty::Medium, Difficulty::Hard, Difficulty::Hardcore];
+
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Medium => "Medium",
+            Difficulty::Hard => "Hard",
+            Difficulty::Hardcore => "Hardcore",
+        }
+    }
+
+    pub(crate) fn tick_interval(self) -> Duration {
+        match self {
+            Difficulty::Easy => Duration::from_millis(220),
+            Difficulty::Medium => Duration::from_millis(150),
+            Difficulty::Hard => Duration::from_millis(90),
+            Difficulty::Hardcore => Duration::from_millis(120),
+        }
+    }
+
+    fn start_length(self) -> u16 {
+        match self {
+            Difficulty::Easy | Difficulty::Medium | Difficulty::Hardcore => 3,
+            Difficulty::Hard => 5,
+        }
+    }
+}
+
+// A starting speed picked with 1 to 4 on the menu, over the difficulty's own
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum SpeedPreset {
+    Slow,
+    Normal,
+    Fast,
+    Insane,
+}
+
+impl SpeedPreset {
+    pub(crate) const ALL: [SpeedPreset; 4] = [SpeedPreset::Slow, SpeedPreset::Normal, SpeedPreset::Fast, SpeedPreset::Insane];
+
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            SpeedPreset::Slow => "Slow",
+            SpeedPreset::Normal => "Normal",
+            SpeedPreset::Fast => "Fast",
+            SpeedPreset::Insane => "Insane",
+        }
+    }
+
+    pub(crate) fn tick_ms(self) -> u64 {
+        match self {
+            SpeedPreset::Slow => 220,
+            SpeedPreset::Normal => 150,
+            SpeedPreset::Fast => 90,
+            SpeedPreset::Insane => 50,
+        }
+    }
+}
+
+// A row on the settings screen
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Setting {
+    Sound,
+    Theme,
+    WrapWalls,
+    WallsKill,
+    Checkerboard,
+    SquareCells,
+    Shadow,
+    RelativeControls,
+    Difficulty,
+}
+
+impl Setting {
+    pub(crate) const ALL: [Setting; 9] = [
+        Setting::Sound,
+        Setting::Theme,
+        Setting::WrapWalls,
+        Setting::WallsKill,
+        Setting::Checkerboard,
+        Setting::SquareCells,
+        Setting::Shadow,
+        Setting::RelativeControls,
+        Setting::Difficulty,
+    ];
+
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            Setting::Sound => "Sound",
+            Setting::Theme => "Theme",
+            Setting::WrapWalls => "Wrap walls",
+            Setting::WallsKill => "Walls kill",
+            Setting::Checkerboard => "Grid overlay",
+            Setting::SquareCells => "Square cells",
+            Setting::Shadow => "Shadow",
+            Setting::RelativeControls => "Relative controls",
+            Setting::Difficulty => "Difficulty",
+        }
+    }
+}
+
+// What reachable_cells depends on besides the head, which the walls keep in
+// the same region
+#[derive(Debug, Clone, PartialEq)]
+struct ReachableKey {
+    obstacles: Vec<Dot>,
+    portals: Option<(Dot, Dot)>,
+    grid: (u16, u16),
+}
+
+#[derive(Debug, Clone)]
+pub struct App {
+    pub(crate) exit: bool,
+    // The first snake is player one, the only one outside two-player mode
+    pub(crate) snakes: Vec<Snake>,
+    // How many tail segments lie on each board cell, kept up to date as the
+    // snakes move so collision checks don't have to walk the tails
+    occupancy: Vec<u16>,
+    pub(crate) players: usize,
+    last_update: Instant,
+    pub(crate) food: Vec<Food>,
+    pub(crate) food_count: usize,
+    // Whole snake length at the start from `--start-length`, None starts with
+    // the difficulty's
+    pub(crate) start_length: Option<u16>,
+    // `--growth`: tail segments every piece of food adds, bonus food still adds
+    // BONUS_GROWTH
+    pub(crate) growth_per_food: u16,
+    // `--start-direction`, None heads away from the nearest wall
+    pub(crate) start_direction: Option<Direction>,
+    pub(crate) bonus_food: bool,
+    pub(crate) combo: bool,
+    // `--scoring classic` makes every piece of food worth one point, the way
+    // older leaderboard entries were scored
+    pub(crate) classic_scoring: bool,
+    pub(crate) points_gained: Option<PointsGained>,
+    // `--drift-food` moves each piece of food a cell this often, on its own
+    // clock rather than the snake's ticks
+    pub(crate) food_drift: Option<Duration>,
+    food_moved_at: Instant,
+    // `--hunger`: going this long without eating starts taking a segment off
+    // the tail every SHRINK_INTERVAL, losing the last one ends the run
+    pub(crate) hunger: Option<Duration>,
+    last_eat: Instant,
+    // `--time-attack`: the run ends once this much time has been played
+    pub(crate) time_attack: Option<Duration>,
+    pub(crate) bonus: Option<BonusFood>,
+    pub(crate) obstacle_layout: ObstacleLayout,
+    pub(crate) obstacles: Vec<Dot>,
+    pub(crate) portals_enabled: bool,
+    pub(crate) portals: Option<(Dot, Dot)>,
+    // The last reachable_cells count and the board it was counted on, the
+    // length bar asks for it every frame but it only moves with the walls
+    reachable_cache: RefCell<Option<(ReachableKey, usize)>>,
+    pub(crate) autopilot: bool,
+    // `--relative-controls`: left and right turn the snake a quarter from its
+    // own heading, up and down do nothing
+    pub(crate) relative_controls: bool,
+    // The autopilot is playing a demo round behind the menu after it sat idle
+    // for ATTRACT_DELAY, nothing it does is recorded
+    pub(crate) demo: bool,
+    last_key: Instant,
+    pub(crate) state: GameState,
+    pub(crate) difficulty: Difficulty,
+    // `--shadow` or the settings screen: a dim shadow a cell below and right
+    // of the snake, on cells nothing else is drawn in
+    pub(crate) shadow: bool,
+    // `--ghost` records player one's moves and races the best run recorded on
+    // this board size
+    pub(crate) ghost: bool,
+    pub(crate) best_run: Option<Ghost>,
+    run_log: Vec<GhostStep>,
+    // `--rival`: the autopilot plays each game's seed through first and runs
+    // alongside as a second ghost
+    pub(crate) rival: bool,
+    pub(crate) rival_run: Option<Ghost>,
+    // What happened since the last tick was handed out by `step`
+    events: Vec<GameEvent>,
+    pub(crate) death_cause: Option<DeathCause>,
+    // Where the run ended, None for a snake that starved
+    pub(crate) crash: Option<Crash>,
+    // `--seed`, every game starts from it when given
+    pub(crate) seed: Option<u64>,
+    // What this game's food was laid out from, `--seed` or one picked at the
+    // start. None for a game continued from a save, its start is gone
+    pub(crate) game_seed: Option<u64>,
+    // `c` on the result put the seed on the clipboard
+    pub(crate) seed_copied: bool,
+    // `--log` appends a line about every finished run to this file
+    pub(crate) log_path: Option<PathBuf>,
+    // The options the game was started with, logged so `--replay` can set the
+    // same game up again
+    pub(crate) args: Vec<String>,
+    // Every turn and every change the clock made to the board since the run
+    // started, for the run log. None for a run continued from a save, whose
+    // start is lost
+    pub(crate) input_log: Option<Vec<ReplayStep>>,
+    // `--replay` is playing a logged run back, nothing it does is recorded
+    pub(crate) replaying: bool,
+    // The snake played on this machine in a networked game, the other one is
+    // steered from across the connection
+    pub(crate) network_player: Option<usize>,
+    // The other end of a networked game went away, the board stays frozen
+    pub(crate) connection_lost: bool,
+    // `--forgiving-neck` lets the head brush the first segment behind it, only
+    // running into the rest of the body kills
+    pub(crate) forgiving_neck: bool,
+    pub(crate) efficiency_goal: Option<EfficiencyGoal>,
+    pub(crate) tick: u64,
+    pub(crate) script: VecDeque<ScriptEvent>,
+    pub(crate) screen_shake: bool,
+    pub(crate) shake_frames: u8,
+    // The board flashes red until then after a death, then the cell the snake
+    // crashed into is picked out for LAST_BREATH before the result popup
+    pub(crate) flash_until: Option<Instant>,
+    // `b` runs the snake at double speed until then
+    pub(crate) boost_until: Option<Instant>,
+    pub(crate) speed_colors: bool,
+    pub(crate) radar: bool,
+    // `--assist` marks the board edge in the direction of far away food
+    pub(crate) assist: bool,
+    pub(crate) warmup_duration: Duration,
+    pub(crate) play_started: Instant,
+    pub(crate) safe_food: bool,
+    pub(crate) hud_position: HudPosition,
+    pub(crate) tongue: bool,
+    // Food pulses between two shades to stand out, `--still-food` holds it still
+    pub(crate) animate_food: bool,
+    // `--debug` or `F1`: the tick counter on the title and an overlay with
+    // coordinates and timings in the board's corner
+    pub(crate) debug: bool,
+    pub(crate) single_step: bool,
+    pub(crate) training: bool,
+    pub(crate) last_input: Instant,
+    pub(crate) margin: u16,
+    pub(crate) frame_times: VecDeque<Duration>,
+    // When the same frames were drawn, for the frame rate on the debug overlay
+    pub(crate) frame_stamps: VecDeque<Instant>,
+    // With the debug overlay up: the last frame drawn and how many cells each
+    // of the last frames sent to the terminal
+    pub(crate) last_frame: Option<Buffer>,
+    pub(crate) redrawn_cells: VecDeque<usize>,
+    pub(crate) constant_speed: bool,
+    pub(crate) heat_map: bool,
+    // `--checkerboard` or `g` shades every other empty cell
+    pub(crate) checkerboard: bool,
+    // `--square-cells` draws every cell two characters wide so the board looks
+    // square, as long as the terminal is wide enough for that
+    pub(crate) square_cells: bool,
+    // `--free-camera` scrolls a board bigger than the terminal along with the
+    // head instead of holding the run until the terminal is big enough
+    pub(crate) free_camera: bool,
+    pub(crate) visits: Vec<u32>,
+    pub(crate) paused: bool,
+    pub(crate) grid_width: u16,
+    pub(crate) grid_height: u16,
+    pub(crate) wrap_walls: bool,
+    pub(crate) walls_kill: bool,
+    // Nothing ends the run: crashes are ignored and the walls only stop the snake
+    pub(crate) zen: bool,
+    pub(crate) chaos: bool,
+    // `--lives`: how many crashes it takes to end a single-player run, the
+    // snake starts over from the start cells after the others
+    pub(crate) lives: Option<u32>,
+    pub(crate) lives_left: u32,
+    pub(crate) death_penalty: Option<DeathPenalty>,
+    // The last penalty taken and when, shown on the title for a moment
+    pub(crate) penalty_taken: Option<(u32, Instant)>,
+    // `--survival`: every plain bite adds a wall somewhere on the board
+    pub(crate) survival: bool,
+    // `--poison`: some of hardcore's food is poison
+    pub(crate) poison: bool,
+    // Tail cut off by poison, on the board as wall until the tick with each cell
+    pub(crate) severed: Vec<(Dot, u64)>,
+    // What `--chaos` rolled for the game under way, none of them without it
+    pub(crate) modifiers: Modifiers,
+    // `--practice`: `u` takes back ticks, up to UNDO_DEPTH of them, and
+    // nothing about the run is kept
+    pub(crate) practice: bool,
+    undo_history: VecDeque<Snapshot>,
+    // Base tick on top of the difficulty's from `--tick-ms`, `SNAKE_TICK_MS` or
+    // the config file, in that order
+    pub(crate) start_tick_ms: Option<u64>,
+    // Base tick interval, starts at the difficulty's pace and +/- tune it live
+    pub(crate) tick_ms: u64,
+    pub(crate) high_score: u32,
+    pub(crate) new_record: bool,
+    pub(crate) leaderboard: Vec<ScoreEntry>,
+    // Top scores are showing instead of the menu or the result popup
+    pub(crate) show_leaderboard: bool,
+    // Where the last run landed on the leaderboard, highlighted there
+    pub(crate) leaderboard_rank: Option<usize>,
+    // The name typed so far after a run that made the leaderboard, keys go here while it is set
+    pub(crate) name_entry: Option<String>,
+    pub(crate) stats: Stats,
+    // Lifetime stats are showing instead of the menu
+    pub(crate) show_stats: bool,
+    // The row the settings screen has picked
+    pub(crate) settings_row: usize,
+    // A run quit mid-game is on disk, the menu offers to continue it
+    pub(crate) saved_game: bool,
+    // Where each difficulty sat on the last frame that showed the menu, for
+    // telling which one a click landed on
+    pub(crate) menu_items: Vec<(Difficulty, Rect)>,
+    pub(crate) sound_enabled: bool,
+    started_at: Instant,
+    paused_at: Option<Instant>,
+    ended_at: Option<Instant>,
+    too_small: bool,
+    // `q` mid-run asks before quitting, the game holds while the question is up
+    pub(crate) confirm_quit: bool,
+    pub(crate) theme: Theme,
+    // The schemes `t` cycles through and which one `theme` is
+    pub(crate) themes: Vec<Theme>,
+    pub(crate) theme_index: usize,
+    pub(crate) keys: KeyBindings,
+    // Food and bonus food, on the game's seed itself
+    pub(crate) rng: StdRng,
+    // Where `--survival` puts its walls
+    wall_rng: StdRng,
+}
+
+impl Default for App {
+    fn default() -> Self {
+        Self {
+            exit: false,
+            snakes: vec![Snake::new(Dot { x: 20, y: 20 }, 3)],
+            players: 1,
+            occupancy: Vec::new(),
+            food: Vec::new(),
+            food_count: 1,
+            start_length: None,
+            growth_per_food: 1,
+            start_direction: None,
+            bonus_food: false,
+            combo: false,
+            classic_scoring: false,
+            points_gained: None,
+            food_drift: None,
+            food_moved_at: Instant::now(),
+            hunger: None,
+            time_attack: None,
+            last_eat: Instant::now(),
+            bonus: None,
+            obstacle_layout: ObstacleLayout::None,
+            obstacles: Vec::new(),
+            portals_enabled: false,
+            portals: None,
+            reachable_cache: RefCell::new(None),
+            autopilot: false,
+            relative_controls: false,
+            last_update: Instant::now(),
+            state: GameState::Menu,
+            difficulty: Difficulty::Medium,
+            shadow: false,
+            ghost: false,
+            best_run: None,
+            rival: false,
+            rival_run: None,
+            run_log: Vec::new(),
+            events: Vec::new(),
+            death_cause: None,
+            crash: None,
+            seed: None,
+            game_seed: None,
+            seed_copied: false,
+            log_path: None,
+            args: Vec::new(),
+            input_log: None,
+            replaying: false,
+            network_player: None,
+            connection_lost: false,
+            forgiving_neck: false,
+            efficiency_goal: None,
+            tick: 0,
+            script: VecDeque::new(),
+            screen_shake: false,
+            shake_frames: 0,
+            flash_until: None,
+            boost_until: None,
+            speed_colors: false,
+            radar: false,
+            assist: false,
+            warmup_duration: Duration::ZERO,
+            play_started: Instant::now(),
+            safe_food: false,
+            hud_position: HudPosition::Top,
+            tongue: false,
+            animate_food: true,
+            debug: false,
+            single_step: false,
+            training: false,
+            last_input: Instant::now(),
+            demo: false,
+            last_key: Instant::now(),
+            margin: 0,
+            frame_times: VecDeque::new(),
+            frame_stamps: VecDeque::new(),
+            last_frame: None,
+            redrawn_cells: VecDeque::new(),
+            constant_speed: false,
+            heat_map: false,
+            checkerboard: false,
+            square_cells: false,
+            free_camera: false,
+            visits: Vec::new(),
+            paused: false,
+            grid_width: DEFAULT_GRID_WIDTH,
+            grid_height: DEFAULT_GRID_HEIGHT,
+            wrap_walls: false,
+            chaos: false,
+            survival: false,
+            poison: false,
+            severed: Vec::new(),
+            lives: None,
+            lives_left: 1,
+            death_penalty: None,
+            penalty_taken: None,
+            modifiers: Modifiers::default(),
+            walls_kill: true,
+            zen: false,
+            practice: false,
+            undo_history: VecDeque::new(),
+            start_tick_ms: None,
+            tick_ms: Difficulty::Medium.tick_interval().as_millis() as u64,
+            high_score: 0,
+            new_record: false,
+            leaderboard: Vec::new(),
+            show_leaderboard: false,
+            leaderboard_rank: None,
+            name_entry: None,
+            stats: Stats::default(),
+            show_stats: false,
+            settings_row: 0,
+            saved_game: false,
+            menu_items: Vec::new(),
+            sound_enabled: true,
+            started_at: Instant::now(),
+            paused_at: None,
+            ended_at: None,
+            too_small: false,
+            confirm_quit: false,
+            theme: Theme::default(),
+            themes: theme_presets(&Theme::default()),
+            theme_index: 0,
+            keys: KeyBindings::default(),
+            rng: StdRng::from_entropy(),
+            wall_rng: StdRng::from_entropy(),
+        }
+    }
+}
+
+// Grid sizes count the whole board, border included
+const DEFAULT_GRID_WIDTH: u16 = 60;
+const DEFAULT_GRID_HEIGHT: u16 = 25;
+const SHAKE_FRAMES: u8 = 4;
+const MAX_QUEUED_TURNS: usize = 3;
+// One in BONUS_CHANCE meals brings out a bonus
+const BONUS_CHANCE: u32 = 4;
+const BONUS_LIFETIME: Duration = Duration::from_secs(5);
+const BONUS_POINTS: u32 = 5;
+const BONUS_GROWTH: u16 = 3;
+// One in this many pieces of food in `--survival` clears walls
+const CLEAR_CHANCE: u32 = 5;
+const CLEAR_RADIUS: u16 = 2;
+// With `--poison`, one in this many bites in hardcore puts poison on the board
+const POISON_CHANCE: u32 = 6;
+const SEVERED_TICKS: u64 = 20;
+// Scaled scoring: food is worth a point, another for every SPEED_POINT_MS the
+// tick is shorter than SCORED_TICK_MS and another for every LENGTH_POINT cells
+// of snake. Easy at the start scores 1, hard 5 and a long snake at the fastest
+// tick well over 10
+const SCORED_TICK_MS: u64 = 230;
+const SPEED_POINT_MS: u64 = 30;
+const LENGTH_POINT: usize = 10;
+const COUNTDOWN: Duration = Duration::from_millis(3500);
+const DEATH_FLASH: Duration = Duration::from_millis(200);
+const LAST_BREATH: Duration = Duration::from_millis(400);
+const BOOST: Duration = Duration::from_millis(1500);
+// Where the rival's game is called off when nothing ended it, zen never does
+const RIVAL_TICKS: usize = 10_000;
+const ATTRACT_DELAY: Duration = Duration::from_secs(20);
+const SHRINK_INTERVAL: Duration = Duration::from_secs(2);
+const COMBO_WINDOW: u64 = 20;
+const MAX_COMBO: u32 = 5;
+// Hardcore's own hunger and drift, used unless `--hunger` or `--drift-food`
+// asks for something else. The hunger grace leaves time to cross the board
+const HARDCORE_HUNGER: Duration = Duration::from_secs(8);
+const HARDCORE_DRIFT: Duration = Duration::from_millis(700);
+// Taken off the tick length by every piece of food eaten in hardcore
+const HARDCORE_RAMP_MS: u64 = 4;
+const WARMUP_START_INTERVAL: Duration = Duration::from_millis(400);
+const SAFE_FOOD_ATTEMPTS: u32 = 200;
+// Ticks practice keeps to take back, older ones are forgotten
+const UNDO_DEPTH: usize = 200;
+// New food keeps at least this many steps from every head, and out of the
+// cells straight ahead of one this far
+const MIN_FOOD_DISTANCE: u16 = 3;
+const FOOD_AHEAD: u16 = 5;
+// About 30 frames a second
+pub(crate) const FRAME_INTERVAL: Duration = Duration::from_millis(33);
+const TRAINING_IDLE: Duration = Duration::from_millis(400);
+const TICK_MS_STEP: u64 = 10;
+pub(crate) const MIN_TICK_MS: u64 = 30;
+pub(crate) const MAX_TICK_MS: u64 = 500;
+// Keeps names inside the leaderboard's name column
+pub(crate) const MAX_NAME_LENGTH: usize = 12;
+
+impl App {
+    pub fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        self.state = GameState::Splash {
+            skip_menu: self.state != GameState::Menu,
+        };
+        // Frames come at a steady FRAME_INTERVAL whatever the tick length, and the
+        // wait for input in between ends early when a tick falls due
+        let mut next_frame = Instant::now();
+        while !self.exit {
+            let frame_start = Instant::now();
+            if frame_start >= next_frame {
+                let (area, drawn) = draw_frame(terminal, |frame| self.draw(frame), self.debug)?;
+                self.record_frame_time(frame_start.elapsed());
+                self.count_redrawn(drawn);
+                self.menu_items = self.menu_item_areas(area);
+                self.set_too_small(!self.fits(area));
+                self.shake_frames = self.shake_frames.saturating_sub(1);
+                // A frame that ran long pushes the next one back instead of
+                // drawing a burst to catch up
+                next_frame = (next_frame + FRAME_INTERVAL).max(frame_start);
+            }
+            let timeout = next_frame.saturating_duration_since(Instant::now()).min(self.until_next_tick());
+            self.handle_events(timeout)?;
+            // The menu and a finished demo round still get updates, either may be
+            // about to start a demo
+            if self.is_running() || self.demo || self.state == GameState::Menu {
+                self.update()?;
+            }
+        }
+        Ok(())
+    }
+
+    // How long until `update` has a tick to run, zero when one is already due
+    pub(crate) fn until_next_tick(&self) -> Duration {
+        match self.state {
+            GameState::Playing if !self.held() && !self.single_step => {
+                (self.last_update + self.tick_interval()).saturating_duration_since(Instant::now())
+            }
+            GameState::Countdown { until } => until.saturating_duration_since(Instant::now()),
+            _ => FRAME_INTERVAL,
+        }
+    }
+
+    // Plays without a terminal or clock, one input per tick until the inputs
+    // run out; after that the snake keeps its heading
+    pub fn run_headless(&mut self, inputs: impl Iterator<Item = Direction>, max_ticks: usize) -> GameOutcome {
+        self.start();
+        let mut inputs = inputs;
+        for _ in 0..max_ticks {
+            if self.state != GameState::Playing || self.exit {
+                break;
+            }
+            self.tick(inputs.next());
+        }
+        self.outcome()
+    }
+
+    // A fresh game that is already under way, without the countdown, for
+    // driving with `tick`
+    pub fn start(&mut self) {
+        self.start_game();
+        self.state = GameState::Playing;
+    }
+
+    // One tick of the game, with `input` turning player one first the way a
+    // key press would. It never looks at the clock, the caller decides when
+    // the next tick is due, and a game that isn't under way doesn't move.
+    // Returns what happened on the tick
+    pub fn tick(&mut self, input: Option<Direction>) -> Vec<GameEvent> {
+        if self.state != GameState::Playing {
+            return Vec::new();
+        }
+        if let Some(direction) = input {
+            self.snakes[0].queue_turn(direction);
+        }
+        self.step()
+    }
+
+    // Where the game stands, Stopped while it is still going
+    pub fn outcome(&self) -> GameOutcome {
+        GameOutcome {
+            score: self.player().score,
+            length: self.length(),
+            ticks: self.tick,
+            end: match self.state {
+                GameState::GameOver => GameEnd::Died,
+                GameState::Won => GameEnd::Won,
+                _ => GameEnd::Stopped,
+            },
+        }
+    }
+
+    // Every cell a snake is on, heads included, once however many segments
+    // share it. Read off the same per-cell counts the collision checks use
+    pub fn occupied_cells(&self) -> HashSet<Dot> {
+        let columns = self.columns() as usize;
+        self.occupancy
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .map(|(index, _)| Dot { x: (index % columns) as u16, y: (index / columns) as u16 })
+            .chain(self.snakes.iter().map(|snake| snake.dot.clone()))
+            .collect()
+    }
+
+    // Every game gets a seed of its own unless `--seed` gave one to all of
+    // them. The same seed lays the food out the same way, which is what lets a
+    // logged run be replayed
+    pub(crate) fn start_game(&mut self) {
+        self.start_seeded(self.seed.unwrap_or_else(rand::random));
+    }
+
+    // `b` on the menu starts on the seed the next game would have had
+    pub(crate) fn browse_seeds(&mut self) {
+        self.preview(self.seed.unwrap_or_else(rand::random));
+    }
+
+    // The seed before or after the one on show, laid out in its place
+    pub(crate) fn browse_next(&mut self, forward: bool) {
+        let seed = self.game_seed.unwrap_or_default();
+        self.preview(if forward { seed.wrapping_add(1) } else { seed.wrapping_sub(1) });
+    }
+
+    // Plays the seed on show, the board laid out again comes out the same
+    pub(crate) fn play_browsed(&mut self) {
+        self.start_seeded(self.game_seed.unwrap_or_default());
+    }
+
+    fn preview(&mut self, seed: u64) {
+        self.start_seeded(seed);
+        self.state = GameState::Browse;
+    }
+
+    fn start_seeded(&mut self, seed: u64) {
+        self.state = GameState::Countdown {
+            until: Instant::now() + COUNTDOWN,
+        };
+        // The whole body is there on the first frame rather than growing out of
+        // the head over the first moves
+        self.snakes = self.start_snakes();
+        self.tick_ms = self.start_tick_ms.unwrap_or(self.difficulty.tick_interval().as_millis() as u64);
+        self.rng = StdRng::seed_from_u64(seed);
+        self.wall_rng = stream(seed, Stream::Walls);
+        self.modifiers = if self.chaos { roll_modifiers(&mut stream(seed, Stream::Modifiers)) } else { Modifiers::default() };
+        if self.modifiers.faster {
+            self.tick_ms = (self.tick_ms * 4 / 5).max(MIN_TICK_MS);
+        }
+        self.game_seed = Some(seed);
+        self.seed_copied = false;
+        self.tick = 0;
+        self.input_log = Some(Vec::new());
+        self.last_update = Instant::now();
+        self.play_started = Instant::now();
+        self.visits = vec![0; self.playable_cells() as usize];
+        self.rebuild_occupancy();
+        self.obstacles = self.build_obstacles();
+        self.portals = self.portals_enabled.then(|| self.build_portals());
+        self.food.clear();
+        self.bonus = None;
+        self.points_gained = None;
+        self.food_moved_at = Instant::now();
+        self.run_log.clear();
+        self.undo_history.clear();
+        self.events.clear();
+        self.death_cause = None;
+        self.crash = None;
+        self.severed.clear();
+        self.lives_left = self.lives.unwrap_or(1);
+        self.penalty_taken = None;
+        for _ in 0..self.food_count {
+            self.spawn_food_randomly();
+        }
+        self.rival_run = (self.rival && self.players == 1).then(|| self.race_rival(seed));
+    }
+
+    // A copy of the game with the autopilot at the wheel plays the same seed
+    // to the end, which only takes its ticks and no clock
+    fn race_rival(&self, seed: u64) -> Ghost {
+        let mut rival = self.clone();
+        rival.rival = false;
+        rival.autopilot = true;
+        rival.ghost = true;
+        rival.demo = true;
+        rival.seed = Some(seed);
+        let outcome = rival.run_headless(std::iter::empty(), RIVAL_TICKS);
+        Ghost {
+            score: outcome.score,
+            steps: rival.run_log,
+        }
+    }
+
+    // Picks a loaded game up paused, with the clock showing the time already
+    // played and no warmup
+    pub(crate) fn resume(&mut self, elapsed: Duration) {
+        let now = Instant::now();
+        self.state = GameState::Playing;
+        self.started_at = now.checked_sub(elapsed).unwrap_or(now);
+        self.play_started = now.checked_sub(self.warmup_duration).unwrap_or(now);
+        self.last_update = now;
+        self.last_eat = now;
+        self.ended_at = None;
+        self.paused = false;
+        self.paused_at = None;
+        self.input_log = None;
+        self.game_seed = None;
+        // There is no seed left to race a rival on
+        self.rival_run = None;
+        self.visits = vec![0; self.playable_cells() as usize];
+        self.rebuild_occupancy();
+        self.toggle_pause();
+    }
+
+    // For tails that appear all at once rather than one move at a time
+    fn rebuild_occupancy(&mut self) {
+        self.occupancy = vec![0; self.playable_cells() as usize];
+        for index in 0..self.snakes.len() {
+            for cell in self.snakes[index].tail.clone() {
+                let cell = self.cell_index(&cell);
+                if let Some(count) = self.occupancy.get_mut(cell) {
+                    *count += 1;
+                }
+            }
+        }
+    }
+
+    fn build_obstacles(&self) -> Vec<Dot> {
+        let columns = self.columns();
+        let rows = self.rows();
+        let mut cells = Vec::new();
+        match self.obstacle_layout {
+            ObstacleLayout::None => {}
+            ObstacleLayout::Pillars => {
+                // A 2x2 block in the middle of each quarter of the board
+                for px in [columns / 4, columns * 3 / 4] {
+                    for py in [rows / 4, rows * 3 / 4] {
+                        for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                            cells.push(Dot { x: px + dx, y: py + dy });
+                        }
+                    }
+                }
+            }
+            ObstacleLayout::Cross => {
+                for x in columns / 4..columns * 3 / 4 {
+                    cells.push(Dot { x, y: rows / 2 });
+                }
+                for y in (rows / 4..rows * 3 / 4).filter(|y| *y != rows / 2) {
+                    cells.push(Dot { x: columns / 2, y });
+                }
+            }
+            ObstacleLayout::Frame => {}
+        }
+
+        // A 3x3 block in the middle of the quarter `--chaos` picked
+        if let Some(quarter) = self.modifiers.cluster {
+            let x = columns / 4 + quarter % 2 * (columns / 2);
+            let y = rows / 4 + quarter / 2 * (rows / 2);
+            for cell in (x - 1..=x + 1).flat_map(|x| (y - 1..=y + 1).map(move |y| Dot { x, y })) {
+                if !cells.contains(&cell) {
+                    cells.push(cell);
+                }
+            }
+        }
+
+        // Keep the start cells and the lanes the snakes first head into clear,
+        // the tails grow out along them
+        let lanes: Vec<Dot> = self.snakes.iter().flat_map(|snake| self.start_lane(snake)).collect();
+        cells.retain(|cell| cell.x < columns && cell.y < rows && !lanes.contains(cell));
+        // The frame goes up whole, `validate_start` keeps the snakes off it
+        if self.obstacle_layout == ObstacleLayout::Frame {
+            let frame: Vec<Dot> = self.frame_cells().filter(|cell| !cells.contains(cell)).collect();
+            cells.extend(frame);
+        }
+        cells
+    }
+
+    // The ring of cells along the inside of the border
+    fn frame_cells(&self) -> impl Iterator<Item = Dot> + use<> {
+        let (columns, rows) = (self.columns(), self.rows());
+        let across = (0..columns).flat_map(move |x| [Dot { x, y: 0 }, Dot { x, y: rows - 1 }]);
+        let down = (1..rows - 1).flat_map(move |y| [Dot { x: 0, y }, Dot { x: columns - 1, y }]);
+        across.chain(down)
+    }
+
+    // Player one starts in the middle of the board, two players a third of the
+    // way in from each side, moved back far enough for a laid out body to fit
+    // behind the head
+    fn start_cells(&self) -> Vec<Dot> {
+        // Inside the frame when there is one
+        let body = self.start_tail() + u16::from(self.obstacle_layout == ObstacleLayout::Frame);
+        let columns = self.columns();
+        let xs = if self.players == 1 { vec![columns / 2] } else { vec![columns / 3, columns * 2 / 3] };
+        let y = self.rows() / 2;
+        xs.into_iter()
+            .map(|x| match self.start_heading() {
+                Direction::Up => Dot { x, y: y.min(self.max_y().saturating_sub(body)) },
+                Direction::Down => Dot { x, y: y.max(body) },
+                Direction::Left => Dot { x: x.min(self.max_x().saturating_sub(body)), y },
+                Direction::Right => Dot { x: x.max(body), y },
+            })
+            .collect()
+    }
+
+    fn start_snakes(&self) -> Vec<Snake> {
+        let heading = self.start_heading();
+        let tail = self.start_tail();
+        self.start_cells()
+            .into_iter()
+            .map(|dot| Snake::laid_out(dot, tail, heading))
+            .collect()
+    }
+
+    // Checks the options lay out a game that can be played before one starts,
+    // so a bad mix of them is an error up front rather than a snake that dies
+    // on its first move
+    pub(crate) fn validate_start(&self) -> Result<(), StartError> {
+        // The body is laid out in a straight line behind the head, it has to
+        // fit on the board with a cell left ahead of the head to move into
+        let (room, cells, side) = match self.start_heading() {
+            Direction::Up | Direction::Down => (self.max_y(), self.rows(), "tall"),
+            Direction::Left | Direction::Right => (self.max_x(), self.columns(), "wide"),
+        };
+        let length = self.start_tail() + 1;
+        if length > room {
+            return Err(StartError::TooLong { length, room, cells, side });
+        }
+
+        let snakes = self.start_snakes();
+        let bodies: Vec<HashSet<Dot>> = snakes
+            .iter()
+            .map(|snake| snake.tail.iter().cloned().chain([snake.dot.clone()]).collect())
+            .collect();
+        if let [one, two] = bodies.as_slice()
+            && !one.is_disjoint(two)
+        {
+            return Err(StartError::SnakesCross);
+        }
+        // The lanes ahead are only a problem when a head points at the other body
+        if let [one, two] = snakes.as_slice()
+            && (bodies[1].contains(&self.ahead(one)) || bodies[0].contains(&self.ahead(two)))
+        {
+            return Err(StartError::SnakesCross);
+        }
+        if self.portals_enabled {
+            let (a, b) = self.build_portals();
+            if bodies.iter().any(|body| body.contains(&a) || body.contains(&b)) {
+                return Err(StartError::OnPortal);
+            }
+        }
+        let mut walls = 0;
+        if self.obstacle_layout == ObstacleLayout::Frame {
+            let frame: HashSet<Dot> = self.frame_cells().collect();
+            let ahead = snakes.iter().map(|snake| self.ahead(snake));
+            if bodies.iter().flatten().cloned().chain(ahead).any(|cell| frame.contains(&cell)) {
+                return Err(StartError::OnFrame);
+            }
+            walls = frame.len();
+        }
+        let free = (self.playable_cells() as usize).saturating_sub(bodies.iter().map(HashSet::len).sum::<usize>() + walls);
+        if self.food_count > free {
+            return Err(StartError::NoRoomForFood { food: self.food_count, free });
+        }
+        Ok(())
+    }
+
+    // The cell a snake moves into next, off the board past a wall
+    fn ahead(&self, snake: &Snake) -> Dot {
+        let Dot { x, y } = snake.dot;
+        match snake.direction {
+            Direction::Up => Dot { x, y: y.wrapping_sub(1) },
+            Direction::Down => Dot { x, y: y + 1 },
+            Direction::Left => Dot { x: x.wrapping_sub(1), y },
+            Direction::Right => Dot { x: x + 1, y },
+        }
+    }
+
+    // Segments behind the head a game starts with
+    fn start_tail(&self) -> u16 {
+        self.start_length.map_or(self.difficulty.start_length(), |length| length - 1)
+    }
+
+    // `--start-direction`, or the way with the most room ahead of the middle
+    // of the board, up when it is a tie. Two snakes side by side only ever
+    // start up or down so they don't run into each other
+    pub(crate) fn start_heading(&self) -> Direction {
+        if let Some(direction) = self.start_direction {
+            return direction;
+        }
+        let (x, y) = (self.columns() / 2, self.rows() / 2);
+        let mut room = vec![(Direction::Up, y), (Direction::Down, self.max_y().saturating_sub(y))];
+        if self.players == 1 {
+            room.extend([(Direction::Left, x), (Direction::Right, self.max_x().saturating_sub(x))]);
+        }
+        // The last of equals wins, so the list is walked backwards
+        room.into_iter()
+            .rev()
+            .max_by_key(|(_, cells)| *cells)
+            .map_or(Direction::Up, |(direction, _)| direction)
+    }
+
+    // One portal near each side wall, halfway down, clear of every wall layout
+    fn build_portals(&self) -> (Dot, Dot) {
+        let columns = self.columns();
+        let rows = self.rows();
+        let y = rows / 2;
+        (Dot { x: columns / 8, y }, Dot { x: columns - 1 - columns / 8, y })
+    }
+
+    pub(crate) fn portal_at(&self, x: u16, y: u16) -> bool {
+        let cell = Dot { x, y };
+        self.portals.as_ref().is_some_and(|(a, b)| *a == cell || *b == cell)
+    }
+
+    fn start_lane(&self, snake: &Snake) -> Vec<Dot> {
+        let length = self.difficulty.start_length() + 2;
+        let head = &snake.dot;
+        (0..=length)
+            .map(|step| match snake.direction {
+                Direction::Up => Dot { x: head.x, y: head.y.saturating_sub(step) },
+                Direction::Down => Dot { x: head.x, y: head.y + step },
+                Direction::Left => Dot { x: head.x.saturating_sub(step), y: head.y },
+                Direction::Right => Dot { x: head.x + step, y: head.y },
+            })
+            .chain(snake.tail.iter().cloned())
+            .collect()
+    }
+
+    pub(crate) fn food_at(&self, x: u16, y: u16) -> Option<usize> {
+        self.food.iter().position(|food| food.x == x && food.y == y)
+    }
+
+    pub(crate) fn player(&self) -> &Snake {
+        &self.snakes[0]
+    }
+
+    pub(crate) fn length(&self) -> usize {
+        self.player().length()
+    }
+
+    // The best score on the board, which is just player one's outside two-player mode
+    pub(crate) fn score(&self) -> u32 {
+        self.snakes.iter().map(|snake| snake.score).max().unwrap_or(0)
+    }
+
+    pub(crate) fn occupied(&self, cell: &Dot) -> bool {
+        self.tail_at(cell) || self.snakes.iter().any(|snake| snake.dot == *cell)
+    }
+
+    fn tail_at(&self, cell: &Dot) -> bool {
+        self.occupancy.get(self.cell_index(cell)).is_some_and(|count| *count > 0)
+    }
+
+    // Position of a board cell in the per-cell `visits` and `occupancy` lists
+    fn cell_index(&self, cell: &Dot) -> usize {
+        cell.y as usize * self.columns() as usize + cell.x as usize
+    }
+
+    pub(crate) fn is_running(&self) -> bool {
+        matches!(self.state, GameState::Playing | GameState::Countdown { .. })
+    }
+
+    // The grid size counts the border, one cell on each side. Everything on the
+    // board lives in the columns and rows inside it, `0..columns()` by `0..rows()`,
+    // which is also the area the board block draws into
+    pub(crate) fn columns(&self) -> u16 {
+        self.grid_width.saturating_sub(2)
+    }
+
+    pub(crate) fn rows(&self) -> u16 {
+        self.grid_height.saturating_sub(2)
+    }
+
+    pub(crate) fn max_x(&self) -> u16 {
+        self.columns().saturating_sub(1)
+    }
+
+    pub(crate) fn max_y(&self) -> u16 {
+        self.rows().saturating_sub(1)
+    }
+
+    // Cells inside the border the head can move through
+    pub(crate) fn playable_cells(&self) -> u16 {
+        self.columns() * self.rows()
+    }
+
+    fn record_visit(&mut self) {
+        let index = self.cell_index(&self.snakes[0].dot);
+        if let Some(count) = self.visits.get_mut(index) {
+            *count += 1;
+        }
+    }
+
+    // Back to a fresh game, options picked at launch stay as they are
+    pub(crate) fn reset(&mut self) {
+        let fresh = Self::default();
+        self.last_update = fresh.last_update;
+        self.tick = fresh.tick;
+        self.shake_frames = fresh.shake_frames;
+        self.flash_until = fresh.flash_until;
+        self.boost_until = fresh.boost_until;
+        self.last_input = fresh.last_input;
+        self.paused = fresh.paused;
+        self.paused_at = fresh.paused_at;
+        self.ended_at = fresh.ended_at;
+        self.new_record = fresh.new_record;
+        self.show_leaderboard = fresh.show_leaderboard;
+        self.leaderboard_rank = fresh.leaderboard_rank;
+        self.name_entry = fresh.name_entry;
+        self.start_game();
+    }
+
+    // Skips the countdown, there is nobody to get ready
+    fn start_demo(&mut self) {
+        self.reset();
+        self.demo = true;
+        let now = Instant::now();
+        self.state = GameState::Playing;
+        self.started_at = now;
+        self.play_started = now;
+        self.last_update = now;
+        self.last_eat = now;
+    }
+
+    // Ends a run or a demo on a fresh menu
+    pub(crate) fn back_to_menu(&mut self) {
+        self.reset();
+        self.demo = false;
+        self.state = GameState::Menu;
+    }
+
+    // Keeps the menu from dropping into the demo while someone is using it
+    pub(crate) fn note_key(&mut self) {
+        self.last_key = Instant::now();
+    }
+
+    pub(crate) fn toggle_pause(&mut self) {
+        self.change_hold(|app| app.paused = !app.paused);
+    }
+
+    // A shrunk terminal holds the game like a pause, without touching the
+    // player's own pause
+    pub(crate) fn set_too_small(&mut self, too_small: bool) {
+        self.change_hold(|app| app.too_small = too_small);
+    }
+
+    pub(crate) fn set_confirm_quit(&mut self, confirm_quit: bool) {
+        self.change_hold(|app| app.confirm_quit = confirm_quit);
+    }
+
+    // The pause, a shrunk terminal and the quit question each hold the game on
+    // their own; the clock stops when the first one starts and runs again once
+    // the last one ends
+    fn change_hold(&mut self, change: impl FnOnce(&mut Self)) {
+        let was_held = self.held();
+        change(self);
+        if self.held() != was_held {
+            self.freeze(!was_held);
+        }
+    }
+
+    fn held(&self) -> bool {
+        self.paused || self.too_small || self.confirm_quit
+    }
+
+    fn freeze(&mut self, frozen: bool) {
+        let now = Instant::now();
+        if frozen {
+            self.paused_at = Some(now);
+        } else {
+            // Start a fresh tick so the snake does not jump forward on resume
+            self.last_update = now;
+            // Push the start forward by the pause so the clock picks up where it stopped
+            if let Some(paused_at) = self.paused_at.take() {
+                self.started_at += now.duration_since(paused_at);
+                if let Some(bonus) = &mut self.bonus {
+                    bonus.spawned_at += now.duration_since(paused_at);
+                }
+                self.food_moved_at += now.duration_since(paused_at);
+                self.last_eat += now.duration_since(paused_at);
+                if let Some(until) = &mut self.boost_until {
+                    *until += now.duration_since(paused_at);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn update(&mut self) -> io::Result<()> {
+        if self.too_small || self.confirm_quit {
+            return Ok(());
+        }
+
+        let now = Instant::now();
+        if self.state == GameState::Menu {
+            if !self.show_leaderboard && !self.show_stats && now.duration_since(self.last_key) >= ATTRACT_DELAY {
+                self.start_demo();
+            }
+            return Ok(());
+        }
+        // A demo round that ended goes straight into the next one
+        if self.demo && !self.is_running() && !self.flashing() {
+            self.start_demo();
+        }
+        if let GameState::Countdown { until } = self.state {
+            if now >= until {
+                self.state = GameState::Playing;
+                self.last_update = now;
+                self.play_started = now;
+                self.started_at = now;
+                self.last_eat = now;
+            }
+            return Ok(());
+        }
+
+        if self.paused {
+            return Ok(());
+        }
+
+        if let Some(bonus) = &self.bonus
+            && now.duration_since(bonus.spawned_at) >= BONUS_LIFETIME
+        {
+            self.bonus = None;
+            self.log_input(ReplayInput::BonusGone);
+        }
+
+        // The food holds still once the tail starts shrinking, a starving snake
+        // shouldn't also have to chase its meal
+        if let Some(drift) = self.drift_every()
+            && self.state == GameState::Playing
+            && !self.starving()
+            && now.duration_since(self.food_moved_at) >= drift
+        {
+            self.drift_food();
+            self.log_input(ReplayInput::Drift);
+            self.food_moved_at = now;
+        }
+
+        if self.time_left() == Some(Duration::ZERO) && self.state == GameState::Playing {
+            self.log_input(ReplayInput::TimeUp);
+            self.time_up();
+            return Ok(());
+        }
+
+        if let Some(hunger) = self.hunger_after()
+            && self.state == GameState::Playing
+            && now.duration_since(self.last_eat) >= hunger + SHRINK_INTERVAL
+        {
+            // The next segment goes one interval later, the grace period is
+            // only waited out once
+            self.last_eat += SHRINK_INTERVAL;
+            self.log_input(ReplayInput::Shrink);
+            self.shrink_tails();
+        }
+
+        // Ticks are counted from when the last one was due rather than when it ran,
+        // so a late loop doesn't slow the snake down. Falling more than a tick
+        // behind starts the count over instead of running several at once
+        let interval = self.tick_interval();
+        if !self.single_step && now.duration_since(self.last_update) >= interval {
+            // In training mode the snake waits in place until the next key press
+            let idle = self.training && now.duration_since(self.last_input) > TRAINING_IDLE;
+            if !idle {
+                self.advance();
+            }
+            self.last_update += interval;
+            if now.duration_since(self.last_update) >= interval {
+                self.last_update = now;
+            }
+        }
+        Ok(())
+    }
+
+    // Runs one tick and then does what it asks of the outside world, so `tick`
+    // and everything below it stay free of terminal and disk access. Keys
+    // have already queued their turns by now
+    pub(crate) fn advance(&mut self) {
+        let events = self.tick(None);
+        if self.demo {
+            return;
+        }
+        if events.iter().any(|event| matches!(event, GameEvent::AteFood { .. })) {
+            self.beep();
+        }
+        if events.iter().any(|event| matches!(event, GameEvent::Died | GameEvent::Won)) {
+            self.record_run();
+        }
+    }
+
+    // Saves what a run that just ended leaves behind, demo rounds, replays and
+    // practice leave nothing
+    fn record_run(&mut self) {
+        if self.demo || self.replaying || self.practice {
+            return;
+        }
+        if self.new_record {
+            shutdown::defer(Store::HighScore(self.high_score));
+        }
+        let beats_ghost = self.best_run.as_ref().is_none_or(|best| self.score() > best.score);
+        if self.ghost && self.players == 1 && beats_ghost {
+            let run = Ghost {
+                score: self.score(),
+                steps: std::mem::take(&mut self.run_log),
+            };
+            let _ = ghost::save(self.grid_width, self.grid_height, &run);
+            self.best_run = Some(run);
+        }
+        let longest = self.snakes.iter().map(Snake::length).max().unwrap_or(0);
+        if let Ok(stats) = stats::record(self.score(), longest) {
+            self.stats = stats;
+        }
+        if let Some(path) = &self.log_path {
+            let _ = history::append(path, self);
+        }
+        // A run that makes the top scores asks for a name before going on the board.
+        // Re-read in case another difficulty's board was showing before the run
+        self.leaderboard = leaderboard::load(self.hardcore());
+        if leaderboard::rank(&self.leaderboard, self.score()).is_some() {
+            self.name_entry = Some(String::new());
+        }
+    }
+
+    // Hunger takes the last segment off every tail, a snake left with only its
+    // head has starved and the run is over, outside zen where nothing ends it
+    pub(crate) fn shrink_tails(&mut self) {
+        for index in 0..self.snakes.len() {
+            let snake = &mut self.snakes[index];
+            snake.tail_length = snake.tail_length.saturating_sub(1);
+            let left = if snake.tail.len() > snake.tail_length as usize {
+                snake.tail.pop_back()
+            } else {
+                None
+            };
+            if let Some(cell) = left {
+                let left = self.cell_index(&cell);
+                if let Some(count) = self.occupancy.get_mut(left) {
+                    *count -= 1;
+                }
+            }
+        }
+        if !self.zen && self.snakes.iter().any(|snake| snake.tail_length == 0) {
+            self.die(DeathCause::Starved);
+            self.record_run();
+        }
+    }
+
+    // `c` on the result puts the game's seed on the clipboard, to pass on to
+    // `--seed`
+    pub(crate) fn copy_seed(&mut self) {
+        if let Some(seed) = self.game_seed
+            && copy_to_clipboard(&seed.to_string()).is_ok()
+        {
+            self.seed_copied = true;
+        }
+    }
+
+    // What is left of `--time-attack`, None without it
+    pub(crate) fn time_left(&self) -> Option<Duration> {
+        self.time_attack.map(|limit| limit.saturating_sub(self.elapsed()))
+    }
+
+    // The clock ends a time attack whatever the snake is doing, zen included
+    pub(crate) fn time_up(&mut self) {
+        self.die(DeathCause::TimeUp);
+        self.record_run();
+    }
+
+    // Puts the run on the leaderboard under the typed name, or the OS user name
+    // when nothing was typed, and shows the board before the result popup
+    pub(crate) fn submit_name(&mut self) {
+        let Some(name) = self.name_entry.take() else {
+            return;
+        };
+        let name = match name.trim() {
+            "" => std::env::var("USER")
+                .or_else(|_| std::env::var("USERNAME"))
+                .unwrap_or_else(|_| "AAA".to_string()),
+            typed => typed.to_string(),
+        };
+        let name: String = name.chars().take(MAX_NAME_LENGTH).collect();
+        if let Ok((entries, rank)) = leaderboard::record(ScoreEntry::new(name, self.score()), self.hardcore()) {
+            self.leaderboard = entries;
+            self.leaderboard_rank = rank;
+            self.show_leaderboard = rank.is_some();
+        }
+    }
+
+    // BEL goes straight to the terminal; it doesn't move the cursor, so the
+    // next ratatui draw is unaffected
+    fn beep(&self) {
+        if self.sound_enabled {
+            let mut stdout = io::stdout();
+            let _ = stdout.write_all(b"\x07").and_then(|_| stdout.flush());
+        }
+    }
+
+    // One game tick, independent of the clock and with no IO. Move first, then
+    // let the body follow and only then look for collisions, so the head is
+    // always checked against this tick's body
+    fn step(&mut self) -> Vec<GameEvent> {
+        if self.practice {
+            self.remember_tick();
+        }
+        if !self.severed.is_empty() {
+            self.decay_severed();
+        }
+        self.apply_script();
+        let headings: Vec<Direction> = self.snakes.iter().map(|snake| snake.direction).collect();
+        for snake in &mut self.snakes {
+            if let Some(direction) = snake.queued_turns.pop_front() {
+                snake.turn(direction);
+            }
+            // The window ran out without another eat
+            if self.tick > snake.combo_until {
+                snake.combo = 0;
+            }
+        }
+        if (self.autopilot || self.demo)
+            && let Some(direction) = self.autopilot_direction()
+        {
+            self.snakes[0].turn(direction);
+        }
+        // Only where each snake ended up heading is logged, the replay sets it
+        // straight
+        for (player, heading) in headings.into_iter().enumerate() {
+            let direction = self.snakes[player].direction;
+            if direction != heading {
+                self.log_input(ReplayInput::Turn { player, direction });
+            }
+        }
+        let mut moved = Vec::new();
+        for index in 0..self.snakes.len() {
+            let previous_head = self.snakes[index].dot.clone();
+            if self.move_dot(index) {
+                self.handle_tail(index, previous_head);
+                moved.push(index);
+            } else if self.walls_kill {
+                self.snakes[index].alive = false;
+            }
+        }
+        self.resolve_eat(moved);
+        self.handle_death();
+        self.record_visit();
+        if self.ghost {
+            let snake = &self.snakes[0];
+            self.run_log.push(GhostStep {
+                x: snake.dot.x,
+                y: snake.dot.y,
+                length: snake.length() as u16,
+            });
+        }
+        self.tick += 1;
+        std::mem::take(&mut self.events)
+    }
+
+    fn remember_tick(&mut self) {
+        if self.undo_history.len() == UNDO_DEPTH {
+            self.undo_history.pop_front();
+        }
+        self.undo_history.push_back(Snapshot {
+            snakes: self.snakes.clone(),
+            occupancy: self.occupancy.clone(),
+            food: self.food.clone(),
+            bonus: self.bonus.clone(),
+            obstacles: self.obstacles.clone(),
+            severed: self.severed.clone(),
+            tick: self.tick,
+            tick_ms: self.tick_ms,
+            rng: self.rng.clone(),
+            wall_rng: self.wall_rng.clone(),
+        });
+    }
+
+    // Puts the board back as it was before the last tick, a crash included,
+    // and gives the next tick a full interval
+    pub(crate) fn undo(&mut self) {
+        if !self.practice || !matches!(self.state, GameState::Playing | GameState::GameOver) {
+            return;
+        }
+        let Some(snapshot) = self.undo_history.pop_back() else {
+            return;
+        };
+        self.snakes = snapshot.snakes;
+        self.occupancy = snapshot.occupancy;
+        self.food = snapshot.food;
+        self.bonus = snapshot.bonus;
+        self.obstacles = snapshot.obstacles;
+        self.severed = snapshot.severed;
+        self.tick = snapshot.tick;
+        self.tick_ms = snapshot.tick_ms;
+        self.rng = snapshot.rng;
+        self.wall_rng = snapshot.wall_rng;
+        self.state = GameState::Playing;
+        self.death_cause = None;
+        self.crash = None;
+        self.ended_at = None;
+        self.flash_until = None;
+        self.points_gained = None;
+        self.last_update = Instant::now();
+    }
+
+    fn log_input(&mut self, input: ReplayInput) {
+        let tick = self.tick;
+        if let Some(log) = &mut self.input_log {
+            log.push(ReplayStep { tick, input });
+        }
+    }
+
+    // Eases from WARMUP_START_INTERVAL down to the base interval over the warmup,
+    // constant speed ignores every modifier and always uses the base interval.
+    // The warmup never starts slower than a base the player tuned down
+    pub(crate) fn tick_interval(&self) -> Duration {
+        let base = Duration::from_millis(self.tick_ms);
+        if self.constant_speed {
+            return base;
+        }
+        let elapsed = self.play_started.elapsed();
+        let interval = if elapsed >= self.warmup_duration {
+            base
+        } else {
+            let start = WARMUP_START_INTERVAL.max(base);
+            let progress = elapsed.as_secs_f64() / self.warmup_duration.as_secs_f64();
+            start - (start - base).mul_f64(progress)
+        };
+        if self.boosting() { interval / 2 } else { interval }
+    }
+
+    pub(crate) fn hardcore(&self) -> bool {
+        self.difficulty == Difficulty::Hardcore
+    }
+
+    // Hardcore turns these on by itself, the command line only adds them for
+    // the other difficulties or changes hardcore's timings
+    pub(crate) fn wraps(&self) -> bool {
+        self.wrap_walls || self.hardcore() || self.modifiers.wrap
+    }
+
+    fn hunger_after(&self) -> Option<Duration> {
+        self.hunger.or(self.hardcore().then_some(HARDCORE_HUNGER))
+    }
+
+    fn drift_every(&self) -> Option<Duration> {
+        self.food_drift.or(self.hardcore().then_some(HARDCORE_DRIFT))
+    }
+
+    // Past the hunger grace, segments are coming off the tail
+    fn starving(&self) -> bool {
+        self.hunger_after()
+            .is_some_and(|hunger| self.paused_at.unwrap_or_else(Instant::now).duration_since(self.last_eat) >= hunger)
+    }
+
+    // Restarts the window when already boosting rather than stacking
+    // There is nothing to boost at constant speed
+    pub(crate) fn boost(&mut self) {
+        if !self.constant_speed {
+            self.boost_until = Some(Instant::now() + BOOST);
+        }
+    }
+
+    pub(crate) fn boosting(&self) -> bool {
+        self.boost_until.is_some_and(|until| self.paused_at.unwrap_or_else(Instant::now) < until)
+    }
+
+    // `faster` shortens the tick by TICK_MS_STEP, otherwise it lengthens it
+    pub(crate) fn adjust_speed(&mut self, faster: bool) {
+        self.tick_ms = if faster {
+            self.tick_ms.saturating_sub(TICK_MS_STEP)
+        } else {
+            self.tick_ms + TICK_MS_STEP
+        }
+        .clamp(MIN_TICK_MS, MAX_TICK_MS);
+        // The tick length now decides what food is worth, a replay has to know
+        self.log_input(ReplayInput::Speed { tick_ms: self.tick_ms });
+    }
+
+    // What a settings row is set to right now
+    pub(crate) fn setting_value(&self, setting: Setting) -> &'static str {
+        let on_off = |on: bool| if on { "on" } else { "off" };
+        match setting {
+            Setting::Sound => on_off(self.sound_enabled),
+            Setting::Theme => THEME_NAMES.get(self.theme_index).copied().unwrap_or("?"),
+            Setting::WrapWalls => on_off(self.wrap_walls),
+            Setting::WallsKill => on_off(self.walls_kill),
+            Setting::Checkerboard => on_off(self.checkerboard),
+            Setting::SquareCells => on_off(self.square_cells),
+            Setting::Shadow => on_off(self.shadow),
+            Setting::RelativeControls => on_off(self.relative_controls),
+            Setting::Difficulty => self.difficulty.label(),
+        }
+    }
+
+    // Flips a switch or steps to the next or previous choice. The screen is
+    // only reachable from the menu and every game reads the rules when it
+    // starts, so nothing here reaches into a run under way
+    pub(crate) fn change_setting(&mut self, setting: Setting, forward: bool) {
+        match setting {
+            Setting::Sound => self.sound_enabled = !self.sound_enabled,
+            Setting::Theme => {
+                let steps = if forward { 1 } else { self.themes.len() - 1 };
+                for _ in 0..steps {
+                    self.cycle_theme();
+                }
+            }
+            Setting::WrapWalls => self.wrap_walls = !self.wrap_walls,
+            Setting::WallsKill => self.walls_kill = !self.walls_kill,
+            Setting::Checkerboard => self.checkerboard = !self.checkerboard,
+            Setting::SquareCells => self.square_cells = !self.square_cells,
+            Setting::Shadow => self.shadow = !self.shadow,
+            Setting::RelativeControls => self.relative_controls = !self.relative_controls,
+            Setting::Difficulty => {
+                let count = Difficulty::ALL.len();
+                let index = Difficulty::ALL.iter().position(|known| *known == self.difficulty).unwrap_or(0);
+                let index = if forward { (index + 1) % count } else { (index + count - 1) % count };
+                self.difficulty = Difficulty::ALL[index];
+            }
+        }
+    }
+
+    // Back to the menu, with the choices left to write to the config file on
+    // the way out
+    pub(crate) fn close_settings(&mut self) {
+        shutdown::defer(Store::Settings(setting_values(self)));
+        self.state = GameState::Menu;
+    }
+
+    // The preset the starting speed is on, None for the difficulty's speed or
+    // a `--tick-ms` that matches none of them
+    pub(crate) fn speed_preset(&self) -> Option<SpeedPreset> {
+        SpeedPreset::ALL.into_iter().find(|preset| self.start_tick_ms == Some(preset.tick_ms()))
+    }
+
+    // Picking the preset already on goes back to the difficulty's speed
+    pub(crate) fn pick_speed(&mut self, preset: SpeedPreset) {
+        self.start_tick_ms = if self.speed_preset() == Some(preset) { None } else { Some(preset.tick_ms()) };
+    }
+
+    pub(crate) fn cycle_theme(&mut self) {
+        self.theme_index = (self.theme_index + 1) % self.themes.len();
+        self.theme = self.themes[self.theme_index].clone();
+        shutdown::defer(Store::Theme(self.theme_index));
+    }
+
+    // Moves the board and every popup on to the border after the board's, in
+    // every theme so `t` doesn't undo it
+    pub(crate) fn cycle_border(&mut self) {
+        let border = self.theme.border.next();
+        for theme in self.themes.iter_mut().chain([&mut self.theme]) {
+            theme.border = border;
+            theme.popup_border = border;
+        }
+    }
+
+    fn apply_script(&mut self) {
+        while let Some(event) = self.script.front() {
+            if event.tick > self.tick {
+                break;
+            }
+            match event.command {
+                ScriptCommand::Turn(direction) => self.snakes[0].queue_turn(direction),
+                ScriptCommand::Pause => self.toggle_pause(),
+                ScriptCommand::Quit => self.exit(),
+            }
+            self.script.pop_front();
+        }
+    }
+
+    // Returns false when a wall stopped the head
+    fn move_dot(&mut self, index: usize) -> bool {
+        let (max_x, max_y) = (self.max_x(), self.max_y());
+        let (columns, rows) = (self.columns(), self.rows());
+        let wraps = self.wraps();
+        let snake = &mut self.snakes[index];
+
+        if wraps {
+            // Leaving one edge comes back in on the opposite edge
+            match snake.direction {
+                Direction::Up => snake.dot.y = (snake.dot.y + rows - 1) % rows,
+                Direction::Right => snake.dot.x = (snake.dot.x + 1) % columns,
+                Direction::Left => snake.dot.x = (snake.dot.x + columns - 1) % columns,
+                Direction::Down => snake.dot.y = (snake.dot.y + 1) % rows,
+            }
+        } else {
+            // One cell per tick in every direction
+            match snake.direction {
+                Direction::Up if snake.dot.y > 0 => snake.dot.y -= 1,
+                Direction::Right if snake.dot.x < max_x => snake.dot.x += 1,
+                Direction::Left if snake.dot.x > 0 => snake.dot.x -= 1,
+                Direction::Down if snake.dot.y < max_y => snake.dot.y += 1,
+                _ => return false,
+            }
+        }
+
+        // Stepping onto a portal comes out of its partner. Only the step in
+        // triggers it, so arriving on the partner doesn't bounce the head back
+        if let Some((a, b)) = &self.portals {
+            if snake.dot == *a {
+                snake.dot = b.clone();
+            } else if snake.dot == *b {
+                snake.dot = a.clone();
+            }
+        }
+        true
+    }
+
+    fn handle_tail(&mut self, index: usize, previous_head: Dot) {
+        let entered = self.cell_index(&previous_head);
+        let snake = &mut self.snakes[index];
+        snake.tail.push_front(previous_head);
+        let left = if snake.tail_length < snake.tail.len() as u16 {
+            snake.tail.pop_back()
+        } else {
+            None
+        };
+
+        if let Some(count) = self.occupancy.get_mut(entered) {
+            *count += 1;
+        }
+        match left {
+            Some(cell) => {
+                let left = self.cell_index(&cell);
+                if let Some(count) = self.occupancy.get_mut(left) {
+                    *count -= 1;
+                }
+            }
+            None => {
+                let length = self.snakes[index].length();
+                self.events.push(GameEvent::Grew { length });
+                // Growth still owed after the last food can be what fills the board
+                if self.food.is_empty() && self.state == GameState::Playing && self.board_filled() {
+                    self.win();
+                }
+            }
+        }
+    }
+
+    // More tail to grow out over the next moves, never more than the board
+    // could hold however much one eat is worth. A zen snake lies over itself
+    // and can be longer than the board
+    fn grow(&mut self, index: usize, segments: u16) {
+        let most = if self.zen { u16::MAX } else { self.playable_cells().saturating_sub(1) };
+        let snake = &mut self.snakes[index];
+        snake.tail_length = snake.tail_length.saturating_add(segments).min(most);
+    }
+
+    // Food is only eaten once every snake has moved, so food put down for one
+    // bite can't be landed on by another head on the same tick. Heads that
+    // reached food eat it top row first and left to right on the board, the
+    // same order on every run whichever player it is, since each bite can
+    // change what the next is worth
+    fn resolve_eat(&mut self, moved: Vec<usize>) {
+        let mut eating: Vec<usize> = moved
+            .into_iter()
+            .filter(|index| {
+                let head = &self.snakes[*index].dot;
+                self.food_at(head.x, head.y).is_some() || self.bonus_at(head.x, head.y)
+            })
+            .collect();
+        eating.sort_by_key(|index| (self.snakes[*index].dot.y, self.snakes[*index].dot.x));
+        for index in eating {
+            self.handle_food(index);
+        }
+    }
+
+    fn handle_food(&mut self, index: usize) {
+        let head = self.snakes[index].dot.clone();
+        if let Some(eaten) = self.food_at(head.x, head.y) {
+            let points = self.food_points(index);
+            let kind = self.food.swap_remove(eaten).kind;
+            if kind == FoodKind::Poison {
+                self.sever(index);
+                return;
+            }
+            self.grow(index, self.growth_per_food);
+            self.last_eat = Instant::now();
+            if self.survival {
+                match kind {
+                    FoodKind::Plain => self.add_wall(),
+                    FoodKind::Clear => self.clear_walls(&head),
+                    FoodKind::Poison => {}
+                }
+            }
+
+            self.spawn_food_randomly();
+            let poisoned = self.food.iter().any(|food| food.kind == FoodKind::Poison);
+            if self.poison && self.hardcore() && !poisoned && self.rng.gen_ratio(1, POISON_CHANCE) {
+                self.spawn_poison();
+            }
+            let tick = self.tick;
+            let multiplier = if self.combo { self.snakes[index].eat_combo(tick) } else { 1 };
+            self.gain_points(index, &head, points * multiplier);
+            self.events.push(GameEvent::AteFood { score: self.snakes[index].score });
+            if self.hardcore() && !self.constant_speed {
+                self.tick_ms = self.tick_ms.saturating_sub(HARDCORE_RAMP_MS).max(MIN_TICK_MS);
+            }
+
+            let chance = if self.modifiers.frequent_bonus { 2 } else { BONUS_CHANCE };
+            if (self.bonus_food || self.modifiers.frequent_bonus) && self.bonus.is_none() && self.rng.gen_ratio(1, chance) {
+                self.spawn_bonus();
+            }
+        } else if self.bonus_at(head.x, head.y) {
+            let points = BONUS_POINTS * self.food_points(index);
+            self.bonus = None;
+            self.last_eat = Instant::now();
+            self.grow(index, BONUS_GROWTH);
+            self.gain_points(index, &head, points);
+            self.events.push(GameEvent::AteFood { score: self.snakes[index].score });
+        } else {
+            return;
+        }
+
+        let snake = &self.snakes[index];
+        if let Some(goal) = &self.efficiency_goal
+            && snake.score >= goal.target_score
+            && snake.length() <= goal.max_length as usize
+        {
+            self.win();
+        }
+    }
+
+    // What a plain piece of food is worth to a snake right now, bonus food is
+    // worth BONUS_POINTS of them
+    pub(crate) fn food_points(&self, index: usize) -> u32 {
+        if self.classic_scoring {
+            return 1;
+        }
+        let speed = SCORED_TICK_MS.saturating_sub(self.tick_ms) / SPEED_POINT_MS;
+        let length = self.snakes[index].length() / LENGTH_POINT;
+        1 + speed as u32 + length as u32
+    }
+
+    fn gain_points(&mut self, index: usize, cell: &Dot, points: u32) {
+        self.snakes[index].score += points;
+        self.points_gained = Some(PointsGained {
+            x: cell.x,
+            y: cell.y,
+            points,
+            at: Instant::now(),
+        });
+    }
+
+    pub(crate) fn bonus_at(&self, x: u16, y: u16) -> bool {
+        self.bonus.as_ref().is_some_and(|bonus| bonus.x == x && bonus.y == y)
+    }
+
+    // Each piece steps to a random free cell next to it, or stays put when
+    // it is boxed in
+    pub(crate) fn drift_food(&mut self) {
+        const DIRECTIONS: [Direction; 4] = [Direction::Up, Direction::Down, Direction::Left, Direction::Right];
+        for index in 0..self.food.len() {
+            let cell = Dot { x: self.food[index].x, y: self.food[index].y };
+            let free: Vec<Dot> = DIRECTIONS
+                .into_iter()
+                .filter_map(|direction| self.neighbour(&cell, direction))
+                .filter(|next| {
+                    !self.occupied(next)
+                        && !self.obstacles.contains(next)
+                        && !self.portal_at(next.x, next.y)
+                        && self.food_at(next.x, next.y).is_none()
+                        && !self.bonus_at(next.x, next.y)
+                })
+                .collect();
+            if free.is_empty() {
+                continue;
+            }
+            let next = &free[self.rng.gen_range(0..free.len())];
+            self.food[index] = Food { x: next.x, y: next.y, kind: self.food[index].kind };
+        }
+    }
+
+    // Poison comes on top of the food rather than in its place, one piece at
+    // a time, so leaving it alone never holds the game up
+    fn spawn_poison(&mut self) {
+        let placed = self.food.len();
+        self.spawn_food_randomly();
+        if let Some(food) = self.food.get_mut(placed) {
+            food.kind = FoodKind::Poison;
+        }
+    }
+
+    // Poison cuts the tail in half. The back half stays where it was as wall
+    // for SEVERED_TICKS and then goes
+    fn sever(&mut self, index: usize) {
+        let snake = &mut self.snakes[index];
+        let keep = snake.tail.len() / 2;
+        let severed = snake.tail.split_off(keep);
+        snake.tail_length = keep as u16;
+        for cell in severed {
+            let left = self.cell_index(&cell);
+            if let Some(count) = self.occupancy.get_mut(left) {
+                *count -= 1;
+            }
+            self.obstacles.push(cell.clone());
+            self.severed.push((cell, self.tick + SEVERED_TICKS));
+        }
+    }
+
+    fn decay_severed(&mut self) {
+        let tick = self.tick;
+        let (gone, kept): (Vec<_>, Vec<_>) = self.severed.drain(..).partition(|(_, until)| *until <= tick);
+        self.severed = kept;
+        self.obstacles.retain(|wall| !gone.iter().any(|(cell, _)| cell == wall));
+    }
+
+    // A survival wall goes wherever food could, never in the way of a head.
+    // With no such cell left the board just doesn't grow one
+    fn add_wall(&mut self) {
+        for _ in 0..SAFE_FOOD_ATTEMPTS {
+            let x = self.wall_rng.gen_range(0..=self.max_x());
+            let y = self.wall_rng.gen_range(0..=self.max_y());
+            let taken = self.occupied(&Dot { x, y })
+                || self.obstacles.contains(&Dot { x, y })
+                || self.portal_at(x, y)
+                || self.food_at(x, y).is_some()
+                || self.bonus_at(x, y)
+                || self.in_snakes_path(x, y);
+            if !taken {
+                self.obstacles.push(Dot { x, y });
+                return;
+            }
+        }
+    }
+
+    // Every wall in the square CLEAR_RADIUS cells around the eaten food
+    fn clear_walls(&mut self, center: &Dot) {
+        self.obstacles
+            .retain(|wall| wall.x.abs_diff(center.x) > CLEAR_RADIUS || wall.y.abs_diff(center.y) > CLEAR_RADIUS);
+    }
+
+    // Bonus food is a treat, not a necessity, so on a crowded board it
+    // simply doesn't show up
+    fn spawn_bonus(&mut self) {
+        for _ in 0..SAFE_FOOD_ATTEMPTS {
+            let x = self.rng.gen_range(0..=self.max_x());
+            let y = self.rng.gen_range(0..=self.max_y());
+            let taken = self.occupied(&Dot { x, y })
+                || self.obstacles.contains(&Dot { x, y })
+                || self.portal_at(x, y)
+                || self.food_at(x, y).is_some();
+            if !taken {
+                self.bonus = Some(BonusFood {
+                    x,
+                    y,
+                    spawned_at: Instant::now(),
+                });
+                return;
+            }
+        }
+    }
+
+    // Cells under a snake, counted once since a zen snake can run over itself.
+    // Counted off the per-cell counts, without building `occupied_cells`
+    fn snake_cells(&self) -> usize {
+        let tails = self.occupancy.iter().filter(|count| **count > 0).count();
+        let heads = self
+            .snakes
+            .iter()
+            .enumerate()
+            .filter(|(index, snake)| !self.tail_at(&snake.dot) && !self.snakes[..*index].iter().any(|other| other.dot == snake.dot))
+            .count();
+        tails + heads
+    }
+
+    // The last food is eaten and the snake covers every cell it could ever get
+    // to, there is nothing left to play for
+    fn board_filled(&self) -> bool {
+        self.food.is_empty() && self.snake_cells() >= self.reachable_cells()
+    }
+
+fn spawn_food_randomly(&mut self) {
+    let max_x = self.max_x();
+    let max_y = self.max_y();
+    let mut attempts = 0;
+
+    if self.board_filled() {
+        self.win();
+        return;
+    }
+
+    // Nowhere left to put it, the food still on the board has to be eaten first
+    let portal_cells = if self.portals.is_some() { 2 } else { 0 };
+    let filled = self.food.len() + self.obstacles.len() + portal_cells + self.bonus.is_some() as usize;
+    if self.snake_cells() + filled >= self.playable_cells() as usize {
+        return;
+    }
+
+    loop {
+        let x = self.rng.gen_range(0..=max_x);
+        let y = self.rng.gen_range(0..=max_y);
+
+        // Check if the generated position conflicts with any snake
+        let conflicts_with_tail = self.occupied(&Dot { x, y });
+
+        let taken = self.food_at(x, y).is_some()
+            || self.bonus_at(x, y)
+            || self.obstacles.contains(&Dot { x, y })
+            || self.portal_at(x, y);
+        if conflicts_with_tail || taken {
+            continue;
+        }
+
+        // Give up on being picky after a while so a crowded board still gets food
+        attempts += 1;
+        if attempts < SAFE_FOOD_ATTEMPTS && self.in_snakes_path(x, y) {
+            continue;
+        }
+        if self.safe_food && attempts < SAFE_FOOD_ATTEMPTS && !self.is_safe_food_cell(x, y) {
+            continue;
+        }
+
+        // If we reach here, the position is valid
+        let kind = if self.survival && self.rng.gen_ratio(1, CLEAR_CHANCE) { FoodKind::Clear } else { FoodKind::Plain };
+        self.food.push(Food { x, y, kind });
+        break;
+    }
+}
+
+    // Too close to a head to be fair: within MIN_FOOD_DISTANCE steps of it, or
+    // in the FOOD_AHEAD cells it is about to run through, where the only way to
+    // dodge it is a sharp turn that might be into the body
+    fn in_snakes_path(&self, x: u16, y: u16) -> bool {
+        self.snakes.iter().any(|snake| {
+            let head = &snake.dot;
+            if head.x.abs_diff(x) + head.y.abs_diff(y) < MIN_FOOD_DISTANCE {
+                return true;
+            }
+            let (along, across) = match snake.direction {
+                Direction::Up => (head.y.checked_sub(y), head.x == x),
+                Direction::Down => (y.checked_sub(head.y), head.x == x),
+                Direction::Left => (head.x.checked_sub(x), head.y == y),
+                Direction::Right => (x.checked_sub(head.x), head.y == y),
+            };
+            across && along.is_some_and(|steps| steps <= FOOD_AHEAD)
+        })
+    }
+
+    // Food is safe when the head can reach it around the body and, once eaten,
+    // there is still more open space around it than the snake is long
+    fn is_safe_food_cell(&self, x: u16, y: u16) -> bool {
+        let from_head = self.open_cells_from(&self.player().dot);
+        let width = self.columns() as usize;
+        if !from_head[y as usize * width + x as usize] {
+            return false;
+        }
+
+        let room = self
+            .open_cells_from(&Dot { x, y })
+            .iter()
+            .filter(|open| **open)
+            .count();
+        room > self.player().tail_length as usize
+    }
+
+    // Board cells player one's head can't enter, indexed row by row
+    fn blocked_cells(&self) -> Vec<bool> {
+        let width = self.columns() as usize;
+        let height = self.rows() as usize;
+        let mut blocked = vec![false; width * height];
+        // A zen snake can go anywhere
+        if self.zen {
+            return blocked;
+        }
+        let other_heads = self.snakes.iter().skip(1).map(|snake| &snake.dot);
+        let tails = self.snakes.iter().flat_map(|snake| &snake.tail);
+        for tail_dot in tails.chain(other_heads).chain(&self.obstacles) {
+            blocked[tail_dot.y as usize * width + tail_dot.x as usize] = true;
+        }
+        // Poison is steered around like a wall
+        for food in self.food.iter().filter(|food| food.kind == FoodKind::Poison) {
+            blocked[food.y as usize * width + food.x as usize] = true;
+        }
+        blocked
+    }
+
+    // The cell one step from `cell`, None when that step leaves a walled board
+    fn neighbour(&self, cell: &Dot, direction: Direction) -> Option<Dot> {
+        let columns = self.columns();
+        let rows = self.rows();
+        let (x, y) = match direction {
+            Direction::Up if cell.y > 0 => (cell.x, cell.y - 1),
+            Direction::Down if cell.y + 1 < rows => (cell.x, cell.y + 1),
+            Direction::Left if cell.x > 0 => (cell.x - 1, cell.y),
+            Direction::Right if cell.x + 1 < columns => (cell.x + 1, cell.y),
+            _ if !self.wraps() => return None,
+            Direction::Up => (cell.x, rows - 1),
+            Direction::Down => (cell.x, 0),
+            Direction::Left => (columns - 1, cell.y),
+            Direction::Right => (0, cell.y),
+        };
+        Some(Dot { x, y })
+    }
+
+    // Breadth-first search from the head to the nearest food around the body and
+    // walls, returning the first step of the shortest path. Without a path any
+    // move that doesn't crash right away will do
+    fn autopilot_direction(&self) -> Option<Direction> {
+        const DIRECTIONS: [Direction; 4] = [Direction::Up, Direction::Down, Direction::Left, Direction::Right];
+        let width = self.columns() as usize;
+        let index = |cell: &Dot| cell.y as usize * width + cell.x as usize;
+        let blocked = self.blocked_cells();
+        let mut first_step: Vec<Option<Direction>> = vec![None; blocked.len()];
+        let mut visited = vec![false; blocked.len()];
+        let player = self.player();
+        visited[index(&player.dot)] = true;
+
+        let mut queue = VecDeque::new();
+        let mut fallback = None;
+        for direction in DIRECTIONS {
+            if direction == player.direction.opposite() {
+                continue;
+            }
+            if let Some(next) = self.neighbour(&player.dot, direction)
+                && !blocked[index(&next)]
+                && !visited[index(&next)]
+            {
+                visited[index(&next)] = true;
+                first_step[index(&next)] = Some(direction);
+                fallback.get_or_insert(direction);
+                queue.push_back(next);
+            }
+        }
+
+        while let Some(cell) = queue.pop_front() {
+            if self.food_at(cell.x, cell.y).is_some() || self.bonus_at(cell.x, cell.y) {
+                return first_step[index(&cell)];
+            }
+            for direction in DIRECTIONS {
+                if let Some(next) = self.neighbour(&cell, direction)
+                    && !blocked[index(&next)]
+                    && !visited[index(&next)]
+                {
+                    visited[index(&next)] = true;
+                    first_step[index(&next)] = first_step[index(&cell)];
+                    queue.push_back(next);
+                }
+            }
+        }
+        fallback
+    }
+
+    // Board cells player one's snake can ever stand on: everything joined to
+    // the head by a path around the walls, portal cells excluded since going
+    // in one comes straight out of the other
+    pub(crate) fn reachable_cells(&self) -> usize {
+        let key = ReachableKey {
+            obstacles: self.obstacles.clone(),
+            portals: self.portals.clone(),
+            grid: (self.grid_width, self.grid_height),
+        };
+        if let Some((cached, count)) = self.reachable_cache.borrow().as_ref()
+            && *cached == key
+        {
+            return *count;
+        }
+        let count = self.count_reachable();
+        *self.reachable_cache.borrow_mut() = Some((key, count));
+        count
+    }
+
+    fn count_reachable(&self) -> usize {
+        const DIRECTIONS: [Direction; 4] = [Direction::Up, Direction::Down, Direction::Left, Direction::Right];
+        let mut visited = vec![false; self.playable_cells() as usize];
+        let start = self.player().dot.clone();
+        visited[self.cell_index(&start)] = true;
+        let mut queue = VecDeque::from([start]);
+        let mut count = 0;
+        while let Some(cell) = queue.pop_front() {
+            count += 1;
+            for next in DIRECTIONS.into_iter().filter_map(|direction| self.neighbour(&cell, direction)) {
+                let index = self.cell_index(&next);
+                if !visited[index] && !self.obstacles.contains(&next) && !self.portal_at(next.x, next.y) {
+                    visited[index] = true;
+                    queue.push_back(next);
+                }
+            }
+        }
+        count
+    }
+
+    // Flood fill over the board from `start`, treating the tail and obstacles as walls
+    fn open_cells_from(&self, start: &Dot) -> Vec<bool> {
+        let width = self.columns() as usize;
+        let height = self.rows() as usize;
+        let blocked = self.blocked_cells();
+
+        let mut visited = vec![false; width * height];
+        let mut queue = VecDeque::from([(start.x as usize, start.y as usize)]);
+        visited[start.y as usize * width + start.x as usize] = true;
+
+        while let Some((x, y)) = queue.pop_front() {
+            let neighbours = [
+                (x.wrapping_sub(1), y),
+                (x + 1, y),
+                (x, y.wrapping_sub(1)),
+                (x, y + 1),
+            ];
+            for (nx, ny) in neighbours {
+                if nx >= width || ny >= height {
+                    continue;
+                }
+                let index = ny * width + nx;
+                if !visited[index] && !blocked[index] {
+                    visited[index] = true;
+                    queue.push_back((nx, ny));
+                }
+            }
+        }
+        visited
+    }
+
+    // A head dies in any body, its own included, in a wall or when it meets
+    // another head, in which case both go. The first death ends the game
+    fn handle_death(&mut self) {
+        if self.zen {
+            return;
+        }
+        // Already down from running into the border with `walls_kill`
+        let mut cause = None;
+        if let Some(snake) = self.snakes.iter().find(|snake| !snake.alive) {
+            cause = Some(DeathCause::Wall);
+            self.crash = Some(Crash {
+                cell: snake.dot.clone(),
+                wall: Some(snake.direction),
+            });
+        }
+        for index in 0..self.snakes.len() {
+            let head = &self.snakes[index].dot;
+            let crashed = if self.obstacles.contains(head) {
+                Some(DeathCause::Wall)
+            } else if (self.tail_at(head) && !self.on_own_neck(index))
+                || self.snakes.iter().enumerate().any(|(other, snake)| other != index && snake.dot == *head)
+            {
+                Some(DeathCause::SelfCollision)
+            } else {
+                None
+            };
+            if let Some(crashed) = crashed {
+                self.snakes[index].alive = false;
+                if cause.is_none() {
+                    self.crash = Some(Crash {
+                        cell: self.snakes[index].dot.clone(),
+                        wall: None,
+                    });
+                }
+                cause.get_or_insert(crashed);
+            }
+        }
+        if self.players == 1 && self.lives_left > 1 && cause.is_some() {
+            self.lose_life();
+        } else if let Some(cause) = cause {
+            self.die(cause);
+        }
+    }
+
+    // A crash with lives to spare: the penalty comes off the score and the
+    // snake is laid out at the start again, the board stays as it is
+    fn lose_life(&mut self) {
+        self.lives_left -= 1;
+        let score = self.snakes[0].score;
+        if let Some(penalty) = self.death_penalty.map(|penalty| penalty.of(score)) {
+            self.snakes[0].score -= penalty;
+            self.penalty_taken = Some((penalty, Instant::now()));
+        }
+        self.snakes[0] = Snake {
+            score: self.snakes[0].score,
+            ..self.start_snakes().swap_remove(0)
+        };
+        self.rebuild_occupancy();
+        self.crash = None;
+        self.events.push(GameEvent::LostLife { lives_left: self.lives_left });
+    }
+
+    // With `--forgiving-neck` the segment right behind the head doesn't count,
+    // as long as no other segment shares that cell
+    fn on_own_neck(&self, index: usize) -> bool {
+        let snake = &self.snakes[index];
+        self.forgiving_neck
+            && snake.tail.front() == Some(&snake.dot)
+            && self.occupancy.get(self.cell_index(&snake.dot)) == Some(&1)
+    }
+
+    fn win(&mut self) {
+        self.state = GameState::Won;
+        self.ended_at = Some(Instant::now());
+        self.events.push(GameEvent::Won);
+        self.record_high_score();
+    }
+
+    fn die(&mut self, cause: DeathCause) {
+        self.death_cause = Some(cause);
+        self.state = GameState::GameOver;
+        self.ended_at = Some(Instant::now());
+        self.events.push(GameEvent::Died);
+        self.record_high_score();
+        // Running out of time isn't a crash, the result shows straight away
+        if cause == DeathCause::TimeUp {
+            return;
+        }
+        let last_breath = if self.crash.is_some() { LAST_BREATH } else { Duration::ZERO };
+        self.flash_until = Some(Instant::now() + DEATH_FLASH + last_breath);
+        if self.screen_shake {
+            self.shake_frames = SHAKE_FRAMES;
+        }
+    }
+
+    // Time spent actually playing, frozen while paused and once the run is over
+    pub(crate) fn elapsed(&self) -> Duration {
+        if let GameState::Countdown { .. } = self.state {
+            return Duration::ZERO;
+        }
+        let until = self.ended_at.or(self.paused_at).unwrap_or_else(Instant::now);
+        until.saturating_duration_since(self.started_at)
+    }
+
+    fn record_high_score(&mut self) {
+        if !self.demo && !self.practice && self.score() > self.high_score {
+            self.high_score = self.score();
+            self.new_record = true;
+        }
+    }
+
+    pub(crate) fn flashing(&self) -> bool {
+        self.flash_until.is_some_and(|until| Instant::now() < until)
+    }
+
+    // The red flash is over and the crash cell is showing
+    pub(crate) fn last_breath(&self) -> bool {
+        self.crash.is_some() && self.flash_until.is_some_and(|until| Instant::now() + LAST_BREATH >= until)
+    }
+
+    pub(crate) fn exit(&mut self) {
+        self.exit = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::{KeyCode, KeyEvent};
+
+    use super::*;
+
+    // A game under way with food ahead of a snake heading up from the middle
+    // of a default board, laid out the same way every time
+    fn started() -> App {
+        let mut app = App {
+            seed: Some(1),
+            start_direction: Some(Direction::Up),
+            ..App::default()
+        };
+        app.start();
+        app
+    }
+
+    // Puts the only food `cells` ahead of player one's head
+    fn food_ahead(app: &mut App, cells: u16) {
+        let head = &app.player().dot;
+        app.food = vec![Food { x: head.x, y: head.y - cells, kind: FoodKind::Plain }];
+    }
+
+    #[test]
+    fn clear_food_takes_only_the_walls_around_it() {
+        let mut app = App { survival: true, ..started() };
+        food_ahead(&mut app, 1);
+        app.food[0].kind = FoodKind::Clear;
+        let Dot { x, y } = app.player().dot;
+        let near = [Dot { x: x + 2, y: y - 3 }, Dot { x: x - 1, y: y - 1 }];
+        let far = [Dot { x: x + 3, y: y - 1 }, Dot { x, y: y - 4 }];
+        app.obstacles = near.iter().chain(&far).cloned().collect();
+        app.tick(None);
+        assert!(app.player().score > 0);
+        assert_eq!(app.obstacles, far);
+    }
+
+    #[test]
+    fn a_plain_bite_in_survival_leaves_a_wall() {
+        let mut app = App { survival: true, ..started() };
+        food_ahead(&mut app, 1);
+        app.obstacles.clear();
+        app.tick(None);
+        assert_eq!(app.obstacles.len(), 1);
+        assert!(!app.in_snakes_path(app.obstacles[0].x, app.obstacles[0].y));
+    }
+
+    #[test]
+    fn the_rival_plays_a_seed_the_same_way_every_time() {
+        let race = || {
+            let mut app = App { rival: true, seed: Some(9), ..App::default() };
+            app.start();
+            app.rival_run.unwrap()
+        };
+        let (first, second) = (race(), race());
+        assert!(first.steps.len() > 1);
+        assert_eq!(first.steps, second.steps);
+        assert_eq!(first.score, second.score);
+    }
+
+    // A started game with lives, player one `score` points in and about to
+    // run into a wall
+    fn crashing_with_lives(lives: u32, penalty: Option<DeathPenalty>, score: u32) -> App {
+        let mut app = App {
+            lives: Some(lives),
+            death_penalty: penalty,
+            ..started()
+        };
+        app.start();
+        app.snakes[0].score = score;
+        let Dot { x, y } = app.player().dot;
+        app.obstacles = vec![Dot { x, y: y - 1 }];
+        app
+    }
+
+    #[test]
+    fn a_life_lost_takes_the_penalty_and_starts_the_snake_over() {
+        let mut app = crashing_with_lives(3, Some(DeathPenalty::Points(5)), 12);
+        let start = app.player().dot.clone();
+        let events = app.tick(None);
+        assert!(events.contains(&GameEvent::LostLife { lives_left: 2 }));
+        assert_eq!(app.state, GameState::Playing);
+        assert_eq!(app.player().score, 7);
+        assert_eq!(app.player().dot, start);
+        assert_eq!(app.penalty_taken.map(|(penalty, _)| penalty), Some(5));
+    }
+
+    #[test]
+    fn the_penalty_stops_at_zero() {
+        let mut app = crashing_with_lives(2, Some(DeathPenalty::Points(5)), 3);
+        app.tick(None);
+        assert_eq!(app.player().score, 0);
+
+        let mut app = crashing_with_lives(2, Some(DeathPenalty::Percent(25)), 10);
+        app.tick(None);
+        assert_eq!(app.player().score, 8);
+        assert_eq!(DeathPenalty::Percent(100).of(7), 7);
+    }
+
+    #[test]
+    fn the_last_life_ends_the_run_with_its_score() {
+        let mut app = crashing_with_lives(1, Some(DeathPenalty::Points(5)), 12);
+        assert!(app.tick(None).contains(&GameEvent::Died));
+        assert_eq!(app.state, GameState::GameOver);
+        assert_eq!(app.player().score, 12);
+    }
+
+    #[test]
+    fn the_warmup_eases_from_slow_down_to_the_base_interval() {
+        let mut app = App {
+            warmup_duration: Duration::from_secs(10),
+            ..started()
+        };
+        let base = Duration::from_millis(app.tick_ms);
+        let after = |app: &mut App, secs: u64| {
+            app.play_started = Instant::now() - Duration::from_secs(secs);
+            app.tick_interval()
+        };
+        let close = |interval: Duration, expected: Duration| interval.abs_diff(expected) < Duration::from_millis(2);
+        assert!(close(after(&mut app, 0), WARMUP_START_INTERVAL));
+        assert!(close(after(&mut app, 5), (WARMUP_START_INTERVAL + base) / 2));
+        assert_eq!(after(&mut app, 10), base);
+        assert_eq!(after(&mut app, 30), base);
+    }
+
+    #[test]
+    fn constant_speed_keeps_the_interval() {
+        let mut app = App {
+            constant_speed: true,
+            difficulty: Difficulty::Hardcore,
+            warmup_duration: Duration::from_secs(10),
+            ..started()
+        };
+        let base = app.tick_interval();
+        assert_eq!(base, Duration::from_millis(app.tick_ms));
+        app.boost();
+        assert_eq!(app.tick_interval(), base);
+        for _ in 0..3 {
+            food_ahead(&mut app, 1);
+            app.tick(None);
+        }
+        assert!(app.player().score > 0);
+        assert_eq!(app.tick_interval(), base);
+    }
+
+    #[test]
+    fn single_step_holds_the_clock_and_f10_steps_once() {
+        let mut app = App { debug: true, ..started() };
+        app.handle_key_event(KeyEvent::from(KeyCode::F(9)));
+        assert!(app.single_step);
+        let tick = app.tick;
+        app.last_update = Instant::now() - app.tick_interval() * 10;
+        app.update().unwrap();
+        assert_eq!(app.tick, tick);
+        app.handle_key_event(KeyEvent::from(KeyCode::F(10)));
+        assert_eq!(app.tick, tick + 1);
+        // Back on the clock the same late update runs the tick it was holding
+        app.handle_key_event(KeyEvent::from(KeyCode::F(9)));
+        app.last_update = Instant::now() - app.tick_interval() * 10;
+        app.update().unwrap();
+        assert_eq!(app.tick, tick + 2);
+    }
+
+    #[test]
+    fn training_waits_for_a_key_once_the_player_goes_idle() {
+        let mut app = App { training: true, ..started() };
+        let late_update = |app: &mut App| {
+            app.last_update = Instant::now() - app.tick_interval();
+            app.update().unwrap();
+            app.player().dot.clone()
+        };
+        app.last_input = Instant::now() - TRAINING_IDLE - Duration::from_millis(50);
+        let held = app.player().dot.clone();
+        for _ in 0..3 {
+            assert_eq!(late_update(&mut app), held);
+        }
+        app.handle_key_event(KeyEvent::from(KeyCode::Left));
+        assert_eq!(late_update(&mut app), Dot { x: held.x - 1, y: held.y });
+    }
+
+    #[test]
+    fn no_food_spawns_when_only_the_bonus_cell_is_free() {
+        let mut app = App {
+            grid_width: 10,
+            grid_height: 10,
+            ..App::default()
+        };
+        app.snakes = vec![Snake::new(Dot { x: 0, y: 0 }, 0)];
+        app.rebuild_occupancy();
+        app.food = vec![Food { x: 7, y: 6, kind: FoodKind::Plain }];
+        app.bonus = Some(BonusFood { x: 7, y: 7, spawned_at: Instant::now() });
+        app.obstacles = (0..8)
+            .flat_map(|x| (0..8).map(move |y| Dot { x, y }))
+            .filter(|cell| ![(0, 0), (7, 6), (7, 7)].contains(&(cell.x, cell.y)))
+            .collect();
+        app.spawn_food_randomly();
+        assert_eq!(app.food.len(), 1);
+    }
+
+    // Every cell of the board once, row by row with every other row reversed,
+    // so each cell is next to the one before it
+    fn serpentine(app: &App) -> Vec<Dot> {
+        (0..app.rows())
+            .flat_map(|y| {
+                let row: Vec<u16> = (0..app.columns()).collect();
+                let row = if y % 2 == 0 { row } else { row.into_iter().rev().collect() };
+                row.into_iter().map(move |x| Dot { x, y })
+            })
+            .collect()
+    }
+
+    // A snake along `path` with its head on the last cell, owing `owed`
+    // segments of growth
+    fn snake_along(app: &mut App, path: &[Dot], direction: Direction, owed: u16) {
+        let (head, tail) = path.split_last().unwrap();
+        let mut snake = Snake::new(head.clone(), tail.len() as u16 + owed);
+        snake.direction = direction;
+        snake.tail = tail.iter().rev().cloned().collect();
+        app.snakes = vec![snake];
+        app.rebuild_occupancy();
+        app.state = GameState::Playing;
+    }
+
+    #[test]
+    fn poison_cuts_the_tail_in_half_and_leaves_the_rest_as_wall_for_a_while() {
+        let mut app = App { poison: true, difficulty: Difficulty::Hardcore, ..started() };
+        let path: Vec<Dot> = (10..=16).rev().map(|y| Dot { x: 20, y }).collect();
+        snake_along(&mut app, &path, Direction::Up, 0);
+        app.obstacles.clear();
+        app.food = vec![Food { x: 20, y: 9, kind: FoodKind::Poison }];
+        app.tick(None);
+        assert_eq!(app.player().score, 0);
+        assert_eq!(app.player().tail, [10, 11, 12].map(|y| Dot { x: 20, y }));
+        let severed = [13, 14, 15].map(|y| Dot { x: 20, y });
+        assert_eq!(app.obstacles, severed);
+        assert!(app.severed.iter().all(|(_, until)| *until == SEVERED_TICKS));
+
+        // Nothing else to eat on the way, the walls only go once their ticks are up
+        app.food.clear();
+        while app.tick < SEVERED_TICKS {
+            assert_eq!(app.obstacles.len(), 3);
+            app.tick(Some(Direction::Left));
+        }
+        app.tick(None);
+        assert_eq!(app.state, GameState::Playing);
+        assert!(app.obstacles.is_empty() && app.severed.is_empty());
+    }
+
+    #[test]
+    fn win_fires_when_the_last_free_cell_is_eaten() {
+        let mut app = App {
+            grid_width: 10,
+            grid_height: 10,
+            ..App::default()
+        };
+        let path = serpentine(&app);
+        let last = path.len() - 1;
+        snake_along(&mut app, &path[..last - 1], Direction::Left, 1);
+        app.food = vec![Food { x: path[last - 1].x, y: path[last - 1].y, kind: FoodKind::Plain }];
+
+        app.tick(None);
+        assert_eq!(app.state, GameState::Playing);
+        assert_eq!(app.food.iter().map(|food| (food.x, food.y)).collect::<Vec<_>>(), [(path[last].x, path[last].y)]);
+
+        let events = app.tick(None);
+        assert_eq!(app.state, GameState::Won);
+        assert!(events.iter().any(|event| matches!(event, GameEvent::Won)));
+    }
+
+    #[test]
+    fn efficiency_goal_wins_only_under_the_length_cap() {
+        for (max_length, won) in [(4, true), (3, false)] {
+            let mut app = App {
+                efficiency_goal: Some(EfficiencyGoal { target_score: 1, max_length }),
+                ..started()
+            };
+            assert_eq!(app.length(), 4);
+            food_ahead(&mut app, 1);
+            app.tick(None);
+            assert!(app.player().score >= 1);
+            assert_eq!(app.state == GameState::Won, won, "cap {max_length}");
+        }
+    }
+
+    #[test]
+    fn occupied_cells_are_the_head_and_tail() {
+        let app = started();
+        let snake = app.player();
+        let mut expected: HashSet<Dot> = snake.tail.iter().cloned().collect();
+        expected.insert(snake.dot.clone());
+        assert_eq!(app.occupied_cells(), expected);
+        assert_eq!(app.snake_cells(), expected.len());
+    }
+
+    // 18 columns by 10 rows inside the border
+    fn board(start_length: u16, start_direction: Direction) -> App {
+        App {
+            grid_width: 20,
+            grid_height: 12,
+            start_length: Some(start_length),
+            start_direction: Some(start_direction),
+            ..App::default()
+        }
+    }
+
+    #[test]
+    fn a_playable_start_validates() {
+        assert_eq!(board(4, Direction::Up).validate_start(), Ok(()));
+        assert_eq!(App { players: 2, ..board(4, Direction::Down) }.validate_start(), Ok(()));
+    }
+
+    #[test]
+    fn a_body_longer_than_the_board_is_refused() {
+        let error = board(10, Direction::Up).validate_start();
+        assert_eq!(error, Err(StartError::TooLong { length: 10, room: 9, cells: 10, side: "tall" }));
+        assert!(board(9, Direction::Up).validate_start().is_ok());
+    }
+
+    #[test]
+    fn two_snakes_laid_over_each_other_are_refused() {
+        let app = App { players: 2, ..board(8, Direction::Right) };
+        assert_eq!(app.validate_start(), Err(StartError::SnakesCross));
+    }
+
+    #[test]
+    fn a_body_over_a_portal_is_refused() {
+        let app = App { portals_enabled: true, ..board(8, Direction::Left) };
+        assert_eq!(app.validate_start(), Err(StartError::OnPortal));
+        let app = App { portals_enabled: true, ..board(4, Direction::Left) };
+        assert!(app.validate_start().is_ok());
+    }
+
+    #[test]
+    fn the_frame_lines_the_border_and_leaves_the_inside_open() {
+        let mut app = App { obstacle_layout: ObstacleLayout::Frame, ..board(4, Direction::Up) };
+        app.start();
+        let (columns, rows) = (app.columns(), app.rows());
+        for x in 0..columns {
+            for y in 0..rows {
+                let ring = x == 0 || y == 0 || x == columns - 1 || y == rows - 1;
+                assert_eq!(app.obstacles.contains(&Dot { x, y }), ring, "{x},{y}");
+            }
+        }
+        assert!(app.food.iter().all(|food| !app.obstacles.contains(&Dot { x: food.x, y: food.y })));
+    }
+
+    #[test]
+    fn a_start_on_the_frame_is_refused() {
+        let app = App { obstacle_layout: ObstacleLayout::Frame, ..board(8, Direction::Up) };
+        assert_eq!(app.validate_start(), Err(StartError::OnFrame));
+        let app = App { obstacle_layout: ObstacleLayout::Frame, ..board(7, Direction::Up) };
+        assert!(app.validate_start().is_ok());
+    }
+
+    #[test]
+    fn food_that_doesnt_fit_beside_the_snake_is_refused() {
+        let app = App { food_count: 177, ..board(4, Direction::Up) };
+        assert_eq!(app.validate_start(), Err(StartError::NoRoomForFood { food: 177, free: 176 }));
+        assert!(App { food_count: 176, ..board(4, Direction::Up) }.validate_start().is_ok());
+    }
+
+    #[test]
+    fn only_perpendicular_presses_queue_a_turn() {
+        let mut snake = Snake::laid_out(Dot { x: 5, y: 5 }, 2, Direction::Up);
+        snake.queue_turn(Direction::Up);
+        assert!(snake.queued_turns.is_empty());
+        snake.queue_turn(Direction::Down);
+        assert!(snake.queued_turns.is_empty());
+        snake.queue_turn(Direction::Left);
+        assert_eq!(snake.queued_turns, [Direction::Left]);
+        // Checked against the queued heading now: left again takes no slot,
+        // right would reverse it and up or down turn it again
+        snake.queue_turn(Direction::Left);
+        snake.queue_turn(Direction::Right);
+        assert_eq!(snake.queued_turns, [Direction::Left]);
+        snake.queue_turn(Direction::Down);
+        assert_eq!(snake.queued_turns, [Direction::Left, Direction::Down]);
+    }
+
+    #[test]
+    fn the_queue_holds_only_so_many_turns() {
+        let mut snake = Snake::laid_out(Dot { x: 5, y: 5 }, 2, Direction::Up);
+        for direction in [Direction::Left, Direction::Up, Direction::Right, Direction::Down] {
+            snake.queue_turn(direction);
+        }
+        assert_eq!(snake.queued_turns.len(), MAX_QUEUED_TURNS);
+        assert_eq!(snake.next_turn(), Some(Direction::Left));
+    }
+
+    fn dots(cells: &[(u16, u16)]) -> Vec<Dot> {
+        cells.iter().map(|&(x, y)| Dot { x, y }).collect()
+    }
+
+    #[test]
+    fn self_collision_fires_on_the_tick_the_head_enters_the_body() {
+        let mut app = board(4, Direction::Up);
+        snake_along(&mut app, &dots(&[(5, 9), (5, 8), (5, 7), (5, 6), (5, 5)]), Direction::Up, 0);
+        app.tick(Some(Direction::Right));
+        app.tick(Some(Direction::Down));
+        assert_eq!(app.state, GameState::Playing);
+        app.tick(Some(Direction::Left));
+        assert_eq!(app.state, GameState::GameOver);
+        assert_eq!(app.death_cause, Some(DeathCause::SelfCollision));
+        assert_eq!(app.tick, 3);
+    }
+
+    #[test]
+    fn the_head_can_follow_the_tail_end_out_of_its_cell() {
+        let mut app = board(4, Direction::Up);
+        snake_along(&mut app, &dots(&[(6, 5), (6, 6), (5, 6), (5, 5)]), Direction::Up, 0);
+        for direction in [Direction::Right, Direction::Down, Direction::Left, Direction::Up] {
+            app.tick(Some(direction));
+            assert_eq!(app.state, GameState::Playing);
+        }
+        assert_eq!(app.player().dot, Dot { x: 5, y: 5 });
+    }
+
+    #[test]
+    fn quick_presses_within_a_tick_never_reverse_the_snake() {
+        let mut app = board(4, Direction::Up);
+        snake_along(&mut app, &dots(&[(5, 2), (5, 3), (5, 4), (5, 5)]), Direction::Down, 0);
+        for direction in [Direction::Down, Direction::Left, Direction::Up] {
+            app.snakes[0].queue_turn(direction);
+        }
+        app.tick(None);
+        assert_eq!(app.player().dot, Dot { x: 4, y: 5 });
+        assert_eq!(app.player().direction, Direction::Left);
+        app.tick(None);
+        assert_eq!(app.player().dot, Dot { x: 4, y: 4 });
+        assert_eq!(app.state, GameState::Playing);
+    }
+
+    #[test]
+    fn eating_grows_the_tail_on_the_next_tick() {
+        let mut app = started();
+        let length = app.player().length();
+        food_ahead(&mut app, 1);
+        app.tick(None);
+        assert_eq!(app.player().tail_length as usize, length);
+        assert_eq!(app.player().length(), length);
+        let events = app.tick(None);
+        assert_eq!(app.player().length(), length + 1);
+        assert!(events.contains(&GameEvent::Grew { length: length + 1 }));
+    }
+
+    #[test]
+    fn running_into_the_body_ends_the_game() {
+        let mut app = board(4, Direction::Up);
+        snake_along(&mut app, &dots(&[(4, 6), (4, 5), (5, 5), (6, 5), (6, 4), (5, 4)]), Direction::Left, 0);
+        let events = app.tick(Some(Direction::Down));
+        assert!(events.contains(&GameEvent::Died));
+        assert_eq!(app.outcome().end, GameEnd::Died);
+        assert!(app.tick(None).is_empty());
+    }
+
+    #[test]
+    fn a_seeded_run_plays_out_the_same_up_to_the_win() {
+        let play = || {
+            let mut app = App {
+                grid_width: 10,
+                grid_height: 10,
+                seed: Some(3),
+                autopilot: true,
+                zen: true,
+                ..App::default()
+            };
+            app.start();
+            while app.outcome().end == GameEnd::Stopped && app.tick < 5000 {
+                app.tick(None);
+            }
+            app
+        };
+        let (first, second) = (play(), play());
+        assert_eq!(first.outcome().end, GameEnd::Won);
+        assert_eq!(first.snake_cells(), first.playable_cells() as usize);
+        assert_eq!(first.tick, second.tick);
+        assert_eq!(first.player().score, second.player().score);
+    }
+
+    #[test]
+    fn the_snake_reaches_every_edge_cell_before_the_wall() {
+        for (direction, edge) in [
+            (Direction::Up, Dot { x: 9, y: 0 }),
+            (Direction::Down, Dot { x: 9, y: 9 }),
+            (Direction::Left, Dot { x: 0, y: 5 }),
+            (Direction::Right, Dot { x: 17, y: 5 }),
+        ] {
+            let mut app = board(2, direction);
+            app.start();
+            app.food.clear();
+            let mut last = app.player().dot.clone();
+            while app.state == GameState::Playing {
+                last = app.player().dot.clone();
+                app.tick(None);
+            }
+            assert_eq!(last, edge);
+            assert_eq!(app.death_cause, Some(DeathCause::Wall));
+        }
+    }
+
+    #[test]
+    fn food_lands_on_every_free_cell_and_nowhere_else() {
+        let mut app = board(2, Direction::Up);
+        app.start();
+        let mut seen = HashSet::new();
+        for _ in 0..5000 {
+            app.food.clear();
+            app.spawn_food_randomly();
+            let food = &app.food[0];
+            assert!(food.x < app.columns() && food.y < app.rows());
+            seen.insert(Dot { x: food.x, y: food.y });
+        }
+        // Cells near the head or right ahead of it are passed over while there
+        // is any choice
+        let free: HashSet<Dot> = (0..app.columns())
+            .flat_map(|x| (0..app.rows()).map(move |y| Dot { x, y }))
+            .filter(|cell| !app.occupied(cell) && !app.in_snakes_path(cell.x, cell.y))
+            .collect();
+        assert!(free.len() > 150);
+        assert!(free.is_subset(&seen));
+    }
+
+    #[test]
+    fn a_tiny_board_never_kills_the_snake_on_its_first_tick() {
+        let directions = [None, Some(Direction::Up), Some(Direction::Down), Some(Direction::Left), Some(Direction::Right)];
+        for difficulty in [Difficulty::Easy, Difficulty::Hard] {
+            for start_direction in directions {
+                // The smallest board the options allow
+                let mut app = App {
+                    grid_width: 10,
+                    grid_height: 10,
+                    difficulty,
+                    start_direction,
+                    seed: Some(1),
+                    ..App::default()
+                };
+                app.start();
+                app.tick(None);
+                assert_eq!(app.state, GameState::Playing, "{difficulty:?} heading {start_direction:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn every_move_counts_a_visit_to_the_cell_the_head_enters() {
+        let mut app = board(2, Direction::Up);
+        app.start();
+        app.food.clear();
+        let start = app.player().dot.clone();
+        let square = [Direction::Right, Direction::Down, Direction::Left, Direction::Up];
+        for direction in square.iter().cycle().take(13) {
+            app.tick(Some(*direction));
+        }
+        let visits = |x: u16, y: u16| app.visits[app.cell_index(&Dot { x, y })];
+        assert_eq!(visits(start.x + 1, start.y), 4);
+        assert_eq!(visits(start.x + 1, start.y + 1), 3);
+        assert_eq!(visits(start.x, start.y + 1), 3);
+        assert_eq!(visits(start.x, start.y), 3);
+        assert_eq!(app.visits.iter().sum::<u32>(), 13);
+    }
+
+    #[test]
+    fn safe_food_keeps_out_of_the_pocket_the_body_walls_off() {
+        // The body runs down the whole board a few columns in, the head is past
+        // it at the bottom and can't get back to the cells left of it
+        let place = |safe_food: bool| {
+            let mut app = board(4, Direction::Up);
+            app.safe_food = safe_food;
+            app.rng = StdRng::seed_from_u64(1);
+            let mut path: Vec<(u16, u16)> = (0..10).map(|y| (5, y)).collect();
+            path.push((6, 9));
+            snake_along(&mut app, &dots(&path), Direction::Right, 0);
+            (0..200)
+                .map(|_| {
+                    app.food.clear();
+                    app.spawn_food_randomly();
+                    app.food[0].x
+                })
+                .collect::<Vec<u16>>()
+        };
+        assert!(place(true).iter().all(|x| *x > 5));
+        assert!(place(false).iter().any(|x| *x < 5));
+    }
+
+    #[test]
+    fn food_eaten_on_one_tick_is_scored_top_row_first() {
+        let mut app = board(2, Direction::Up);
+        app.difficulty = Difficulty::Hardcore;
+        app.players = 2;
+        app.seed = Some(1);
+        app.start();
+        // One tick short of a speed point, which the first bite's speed up earns
+        app.tick_ms = SCORED_TICK_MS - SPEED_POINT_MS + 1;
+        app.snakes = vec![
+            Snake::laid_out(Dot { x: 4, y: 6 }, 2, Direction::Up),
+            Snake::laid_out(Dot { x: 10, y: 4 }, 2, Direction::Up),
+        ];
+        app.rebuild_occupancy();
+        app.food = vec![Food { x: 4, y: 5, kind: FoodKind::Plain }, Food { x: 10, y: 3, kind: FoodKind::Plain }];
+        let events = app.tick(None);
+        let scores: Vec<u32> = events
+            .iter()
+            .filter_map(|event| match event {
+                GameEvent::AteFood { score } => Some(*score),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(scores, [1, 2]);
+        assert_eq!((app.snakes[0].score, app.snakes[1].score), (2, 1));
+        assert_eq!(app.tick_ms, SCORED_TICK_MS - SPEED_POINT_MS + 1 - 2 * HARDCORE_RAMP_MS);
+    }
+
+    #[test]
+    fn the_seed_browser_plays_the_board_it_shows() {
+        let mut app = App { seed: Some(40), ..App::default() };
+        app.browse_seeds();
+        assert_eq!((app.state, app.game_seed), (GameState::Browse, Some(40)));
+        app.browse_next(true);
+        app.browse_next(true);
+        app.browse_next(false);
+        assert_eq!((app.state, app.game_seed), (GameState::Browse, Some(41)));
+        let shown: Vec<(u16, u16)> = app.food.iter().map(|food| (food.x, food.y)).collect();
+
+        app.handle_key_event(KeyEvent::from(KeyCode::Enter));
+        assert!(matches!(app.state, GameState::Countdown { .. }));
+        assert_eq!(app.game_seed, Some(41));
+        assert_eq!(app.food.iter().map(|food| (food.x, food.y)).collect::<Vec<_>>(), shown);
+    }
+
+    #[test]
+    fn a_seed_rolls_the_same_modifiers_every_time() {
+        let roll = |seed: u64| roll_modifiers(&mut StdRng::seed_from_u64(seed));
+        assert_eq!(roll(5), roll(5));
+        assert_eq!(roll(5).labels().len(), 2);
+        let rolls: HashSet<Vec<&str>> = (0..20).map(|seed| roll(seed).labels()).collect();
+        assert!(rolls.len() > 1);
+
+        let start = |seed: u64| {
+            let mut app = App { chaos: true, seed: Some(seed), ..App::default() };
+            app.start();
+            (app.modifiers, app.obstacles, app.tick_ms)
+        };
+        assert_eq!(start(5), start(5));
+        assert_ne!(start(5).0, start(6).0);
+    }
+
+    #[test]
+    fn each_stream_plays_the_same_from_the_same_seed() {
+        let draws = |mut rng: StdRng| (0..8).map(|_| rng.r#gen::<u64>()).collect::<Vec<u64>>();
+        for name in [Stream::Modifiers, Stream::Walls] {
+            assert_eq!(draws(stream(7, name)), draws(stream(7, name)));
+            assert_ne!(draws(stream(7, name)), draws(stream(8, name)));
+        }
+        let all = [draws(stream(7, Stream::Modifiers)), draws(stream(7, Stream::Walls)), draws(StdRng::seed_from_u64(7))];
+        assert_eq!(all.iter().collect::<HashSet<_>>().len(), all.len());
+    }
+
+    #[test]
+    fn walls_drawn_leave_the_food_where_it_would_have_been() {
+        let start = || {
+            let mut app = App { survival: true, ..started() };
+            app.obstacles.clear();
+            app
+        };
+        let (mut walled, mut plain) = (start(), start());
+        for _ in 0..5 {
+            walled.add_wall();
+        }
+        assert_eq!(walled.obstacles.len(), 5);
+        assert_eq!(walled.rng.r#gen::<u64>(), plain.rng.r#gen::<u64>());
+        assert_ne!(walled.wall_rng.r#gen::<u64>(), plain.wall_rng.r#gen::<u64>());
+    }
+}
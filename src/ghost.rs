@@ -0,0 +1,61 @@
+use std::io;
+use std::path::PathBuf;
+
+use crate::config::data_dir;
+
+// Where player one's head went on one tick and how long the snake was after it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct GhostStep {
+    pub(crate) x: u16,
+    pub(crate) y: u16,
+    pub(crate) length: u16,
+}
+
+// The best run so far, replayed a step per tick behind the live snake
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Ghost {
+    pub(crate) score: u32,
+    pub(crate) steps: Vec<GhostStep>,
+}
+
+fn ghost_path() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join("best_run"))
+}
+
+// None when nothing was recorded yet, the file doesn't parse, or the run was
+// played on a board of another size and would wander through walls here
+pub(crate) fn load(width: u16, height: u16) -> Option<Ghost> {
+    let contents = std::fs::read_to_string(ghost_path()?).ok()?;
+    let mut lines = contents.lines();
+    if lines.next()? != format!("grid {width} {height}") {
+        return None;
+    }
+    let score = lines.next()?.strip_prefix("score ")?.parse().ok()?;
+    let steps = lines
+        .map(|line| {
+            let mut numbers = line.split_whitespace().map(|number| number.parse().ok());
+            Some(GhostStep {
+                x: numbers.next()??,
+                y: numbers.next()??,
+                length: numbers.next()??,
+            })
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(Ghost { score, steps })
+}
+
+// A `grid` and a `score` line, then one `x y length` line per tick. Renamed over
+// the old file so a crash mid-write keeps the previous best
+pub(crate) fn save(width: u16, height: u16, ghost: &Ghost) -> io::Result<()> {
+    let path = ghost_path().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no data directory"))?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut contents = format!("grid {width} {height}\nscore {}\n", ghost.score);
+    for step in &ghost.steps {
+        contents.push_str(&format!("{} {} {}\n", step.x, step.y, step.length));
+    }
+    let temporary = path.with_extension(std::process::id().to_string());
+    std::fs::write(&temporary, contents)?;
+    std::fs::rename(&temporary, &path)
+}
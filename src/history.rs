@@ -0,0 +1,39 @@
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::game::{App, GameState};
+use crate::json::json_strings;
+
+// One JSON object per line for every finished run. Fields are only ever added,
+// never renamed or dropped, so old logs aggregate with new ones:
+// seed (the game's own, null for a run continued from a save), duration_ms, score, length, end ("died" or
+// "won"), cause (null for a win), grid_width, grid_height, difficulty, args
+// (the options the game was started with) and inputs (what `--replay` feeds
+// back in, null for a run continued from a save)
+pub(crate) fn append(path: &Path, app: &App) -> io::Result<()> {
+    let seed = app.game_seed.map_or_else(|| "null".to_string(), |seed| seed.to_string());
+    let end = if app.state == GameState::Won { "won" } else { "died" };
+    let cause = app
+        .death_cause
+        .filter(|_| app.state == GameState::GameOver)
+        .map_or_else(|| "null".to_string(), |cause| format!("\"{}\"", cause.label()));
+    let inputs = app.input_log.as_ref().map_or_else(
+        || "null".to_string(),
+        |log| json_strings(&log.iter().map(|step| step.encode()).collect::<Vec<_>>()),
+    );
+    let line = format!(
+        "{{\"seed\": {seed}, \"duration_ms\": {}, \"score\": {}, \"length\": {}, \"end\": \"{end}\", \"cause\": {cause}, \"grid_width\": {}, \"grid_height\": {}, \"difficulty\": \"{}\", \"args\": {}, \"inputs\": {inputs}}}\n",
+        app.elapsed().as_millis(),
+        app.score(),
+        app.length(),
+        app.grid_width,
+        app.grid_height,
+        app.difficulty.label().to_lowercase(),
+        json_strings(&app.args),
+    );
+    // A single write to a file opened for appending, runs from two games at
+    // once land on lines of their own
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(line.as_bytes())
+}
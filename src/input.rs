@@ -0,0 +1,473 @@
+use std::collections::VecDeque;
+use std::io;
+use std::time::{Duration, Instant};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, MouseButton, MouseEvent, MouseEventKind};
+use ratatui::layout::Position;
+
+use crate::game::{App, Difficulty, Direction, GameState, Setting, SpeedPreset, MAX_NAME_LENGTH};
+use crate::shutdown::{self, Store};
+use crate::{leaderboard, save};
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum ScriptCommand {
+    Turn(Direction),
+    // Holds the game the way the pause key does, until it is pressed
+    Pause,
+    Quit,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct ScriptEvent {
+    pub(crate) tick: u64,
+    pub(crate) command: ScriptCommand,
+}
+
+// What a remappable key does, whichever key it is
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Action {
+    Up,
+    Down,
+    Left,
+    Right,
+    Pause,
+    Quit,
+    Restart,
+}
+
+impl Action {
+    pub(crate) const ALL: [Action; 7] = [
+        Action::Up,
+        Action::Down,
+        Action::Left,
+        Action::Right,
+        Action::Pause,
+        Action::Quit,
+        Action::Restart,
+    ];
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Action::Up => "up",
+            Action::Down => "down",
+            Action::Left => "left",
+            Action::Right => "right",
+            Action::Pause => "pause",
+            Action::Quit => "quit",
+            Action::Restart => "restart",
+        }
+    }
+}
+
+// Player one's steering, pause, quit and restart, which the config file can
+// remap. WASD for player two and the toggle keys stay where they are
+#[derive(Debug, Clone)]
+pub(crate) struct KeyBindings {
+    bindings: Vec<(KeyCode, Action)>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            bindings: vec![
+                (KeyCode::Up, Action::Up),
+                (KeyCode::Char('k'), Action::Up),
+                (KeyCode::Down, Action::Down),
+                (KeyCode::Char('j'), Action::Down),
+                (KeyCode::Left, Action::Left),
+                (KeyCode::Char('h'), Action::Left),
+                (KeyCode::Right, Action::Right),
+                (KeyCode::Char('l'), Action::Right),
+                (KeyCode::Char(' '), Action::Pause),
+                (KeyCode::Char('q'), Action::Quit),
+                (KeyCode::Char('r'), Action::Restart),
+            ],
+        }
+    }
+}
+
+impl KeyBindings {
+    pub(crate) fn action(&self, code: KeyCode) -> Option<Action> {
+        self.bindings.iter().find(|(key, _)| *key == code).map(|(_, action)| *action)
+    }
+
+    pub(crate) fn keys(&self, action: Action) -> impl Iterator<Item = KeyCode> + '_ {
+        self.bindings.iter().filter(move |(_, bound)| *bound == action).map(|(key, _)| *key)
+    }
+
+    // The given keys take over from the ones `action` had before
+    pub(crate) fn bind(&mut self, action: Action, keys: Vec<KeyCode>) {
+        self.bindings.retain(|(_, bound)| *bound != action);
+        self.bindings.extend(keys.into_iter().map(|key| (key, action)));
+    }
+
+    // The first key that ended up on two actions, if any
+    pub(crate) fn conflict(&self) -> Option<(KeyCode, Action, Action)> {
+        self.bindings.iter().enumerate().find_map(|(index, (key, action))| {
+            self.bindings[index + 1..]
+                .iter()
+                .find(|(other_key, other)| other_key == key && other != action)
+                .map(|(_, other)| (*key, *action, *other))
+        })
+    }
+}
+
+impl App {
+    // Waits up to `timeout` for input, then takes every event already queued so a
+    // burst of keys is handled in one go
+    pub(crate) fn handle_events(&mut self, timeout: Duration) -> io::Result<()> {
+        let mut timeout = timeout;
+        while event::poll(timeout)? {
+            match event::read()? {
+                Event::Key(key_event) if key_event.kind == KeyEventKind::Press => {
+                    self.handle_key_event(key_event)
+                }
+                Event::Mouse(mouse_event) => self.handle_mouse_event(mouse_event),
+                Event::FocusLost => self.focus_lost(),
+                _ => {}
+            }
+            timeout = Duration::ZERO;
+        }
+        Ok(())
+    }
+
+    // Switching away pauses the run, coming back leaves it paused until the
+    // player resumes it so the snake doesn't move off before they are ready
+    pub(crate) fn focus_lost(&mut self) {
+        if self.state == GameState::Playing && !self.paused && !self.demo {
+            self.toggle_pause();
+        }
+    }
+
+    // A left click on a menu difficulty picks it and starts the game. During the
+    // demo the click only brings the menu back, like a key would
+    fn handle_mouse_event(&mut self, mouse_event: MouseEvent) {
+        if mouse_event.kind != MouseEventKind::Down(MouseButton::Left) {
+            return;
+        }
+        self.note_key();
+        if self.demo {
+            self.back_to_menu();
+            return;
+        }
+        if self.state != GameState::Menu {
+            return;
+        }
+        let position = Position::new(mouse_event.column, mouse_event.row);
+        if let Some((difficulty, _)) = self.menu_items.iter().find(|(_, area)| area.contains(position)) {
+            self.difficulty = *difficulty;
+            self.start_game();
+        }
+    }
+
+    pub(crate) fn handle_key_event(&mut self, key_event: KeyEvent) {
+        self.note_key();
+        if self.demo {
+            self.back_to_menu();
+            return;
+        }
+
+        // Any key cuts the death flash short, and does nothing else
+        if self.flashing() {
+            self.flash_until = None;
+            return;
+        }
+
+        if key_event.code == KeyCode::Esc {
+            self.back();
+            return;
+        }
+
+        if let GameState::Splash { skip_menu } = self.state {
+            match (self.keys.action(key_event.code), key_event.code) {
+                (Some(Action::Quit), _) => self.exit(),
+                (_, KeyCode::Enter) if skip_menu => self.start_game(),
+                (_, KeyCode::Enter) => self.state = GameState::Menu,
+                _ => {}
+            }
+            return;
+        }
+
+        if self.state == GameState::Menu {
+            self.handle_menu_key(key_event);
+            return;
+        }
+
+        if self.state == GameState::Settings {
+            self.handle_settings_key(key_event);
+            return;
+        }
+
+        if self.state == GameState::Browse {
+            match (self.keys.action(key_event.code), key_event.code) {
+                (Some(Action::Quit), _) => self.exit(),
+                (Some(Action::Left), _) | (_, KeyCode::Char('a' | 'A')) => self.browse_next(false),
+                (Some(Action::Right), _) | (_, KeyCode::Char('d' | 'D')) => self.browse_next(true),
+                (_, KeyCode::Enter) => self.play_browsed(),
+                _ => {}
+            }
+            return;
+        }
+
+        if let Some(name) = &mut self.name_entry {
+            match key_event.code {
+                KeyCode::Char(character) if !character.is_control() && name.chars().count() < MAX_NAME_LENGTH => {
+                    name.push(character)
+                }
+                KeyCode::Backspace => {
+                    name.pop();
+                }
+                KeyCode::Enter => self.submit_name(),
+                _ => {}
+            }
+            return;
+        }
+
+        if self.confirm_quit {
+            match key_event.code {
+                KeyCode::Char('y' | 'Y') => {
+                    shutdown::defer(Store::SavedGame(save::saved_game(self)));
+                    self.exit();
+                }
+                KeyCode::Char('n' | 'N') => self.set_confirm_quit(false),
+                _ => {}
+            }
+            return;
+        }
+
+        let action = self.keys.action(key_event.code);
+        if matches!(self.state, GameState::GameOver | GameState::Won) {
+            match (action, key_event.code) {
+                (Some(Action::Restart), _) => self.reset(),
+                (Some(Action::Quit), _) => self.exit(),
+                (_, KeyCode::Char('u')) => self.undo(),
+                (_, KeyCode::Char('c')) => self.copy_seed(),
+                (_, KeyCode::Char('l')) => self.show_leaderboard = !self.show_leaderboard,
+                (_, KeyCode::Enter) => self.show_leaderboard = false,
+                _ => {}
+            }
+            return;
+        }
+        
+        // Arrows, WASD and vim-style hjkl all steer the snake by default, the
+        // bound keys first. With two players WASD belongs to player two.
+        // Relative controls only take left and right, and `,` and `.`
+        let wasd = self.snakes.len() - 1;
+        let turn = if self.relative_controls {
+            match (action, key_event.code) {
+                (Some(Action::Left), _) | (_, KeyCode::Char(',')) => Some((0, false)),
+                (Some(Action::Right), _) | (_, KeyCode::Char('.')) => Some((0, true)),
+                (_, KeyCode::Char('a' | 'A')) => Some((wasd, false)),
+                (_, KeyCode::Char('d' | 'D')) => Some((wasd, true)),
+                _ => None,
+            }
+            .map(|(player, clockwise)| (player, self.snakes[player].rotated(clockwise)))
+        } else {
+            match (action, key_event.code) {
+                (Some(Action::Left), _) => Some((0, Direction::Left)),
+                (Some(Action::Right), _) => Some((0, Direction::Right)),
+                (Some(Action::Up), _) => Some((0, Direction::Up)),
+                (Some(Action::Down), _) => Some((0, Direction::Down)),
+                (_, KeyCode::Char('a' | 'A')) => Some((wasd, Direction::Left)),
+                (_, KeyCode::Char('d' | 'D')) => Some((wasd, Direction::Right)),
+                (_, KeyCode::Char('w' | 'W')) => Some((wasd, Direction::Up)),
+                (_, KeyCode::Char('s' | 'S')) => Some((wasd, Direction::Down)),
+                _ => None,
+            }
+        };
+        if let Some((player, direction)) = turn {
+            // The autopilot does player one's steering, their keys are ignored
+            if !(self.autopilot && player == 0) {
+                self.last_input = Instant::now();
+                self.snakes[player].queue_turn(direction);
+            }
+            return;
+        }
+
+        match (action, key_event.code) {
+            (Some(Action::Quit), _) => self.set_confirm_quit(true),
+            (Some(Action::Pause), _) if self.state == GameState::Playing => self.toggle_pause(),
+            (Some(_), _) => {}
+            (_, KeyCode::Char('m')) => self.sound_enabled = !self.sound_enabled,
+            (_, KeyCode::Char('t')) => self.cycle_theme(),
+            (_, KeyCode::Char('o')) => self.cycle_border(),
+            (_, KeyCode::Char('g')) => self.checkerboard = !self.checkerboard,
+            (_, KeyCode::Char('u')) => self.undo(),
+            (_, KeyCode::Char('b')) if self.state == GameState::Playing && !self.paused => self.boost(),
+            (_, KeyCode::Char('+')) => self.adjust_speed(true),
+            (_, KeyCode::Char('-')) => self.adjust_speed(false),
+            (_, KeyCode::F(1)) => {
+                self.debug = !self.debug;
+                self.single_step &= self.debug;
+            }
+            (_, KeyCode::F(9)) if self.debug => self.single_step = !self.single_step,
+            (_, KeyCode::F(10)) if self.single_step && self.state == GameState::Playing => self.advance(),
+            _ => {}
+        }
+    }
+
+    // Esc backs out one level wherever it is pressed, except on the splash with
+    // nothing before it: a menu screen or the settings to the menu,
+    // the quit question back to the game, the name prompt and the leaderboard
+    // to the result, the result to the menu and a run to the quit question
+    fn back(&mut self) {
+        match self.state {
+            GameState::Splash { .. } => {}
+            GameState::Menu => {
+                self.show_leaderboard = false;
+                self.show_stats = false;
+            }
+            GameState::Settings => self.close_settings(),
+            GameState::Browse => self.back_to_menu(),
+            _ if self.confirm_quit => self.set_confirm_quit(false),
+            GameState::GameOver | GameState::Won if self.name_entry.is_some() => self.name_entry = None,
+            GameState::GameOver | GameState::Won if self.show_leaderboard => self.show_leaderboard = false,
+            GameState::GameOver | GameState::Won => self.back_to_menu(),
+            GameState::Playing | GameState::Countdown { .. } => self.set_confirm_quit(true),
+        }
+    }
+
+    fn handle_menu_key(&mut self, key_event: KeyEvent) {
+        if self.show_leaderboard || self.show_stats {
+            match (self.keys.action(key_event.code), key_event.code) {
+                (Some(Action::Quit), _) => self.exit(),
+                (_, KeyCode::Char('l' | 'i') | KeyCode::Enter) => {
+                    self.show_leaderboard = false;
+                    self.show_stats = false;
+                }
+                _ => {}
+            }
+            return;
+        }
+        let selected = Difficulty::ALL
+            .iter()
+            .position(|difficulty| *difficulty == self.difficulty)
+            .unwrap_or(0);
+        match (self.keys.action(key_event.code), key_event.code) {
+            (Some(Action::Quit), _) => self.exit(),
+            (Some(Action::Up), _) | (_, KeyCode::Char('w' | 'W')) => {
+                self.difficulty = Difficulty::ALL[selected.saturating_sub(1)]
+            }
+            (Some(Action::Down), _) | (_, KeyCode::Char('s' | 'S')) => {
+                self.difficulty = Difficulty::ALL[(selected + 1).min(Difficulty::ALL.len() - 1)]
+            }
+            (_, KeyCode::Enter) => self.start_game(),
+            (_, KeyCode::Char(digit @ '1'..='4')) => {
+                self.pick_speed(SpeedPreset::ALL[digit as usize - '1' as usize])
+            }
+            (_, KeyCode::Char('l')) => {
+                // The board for the difficulty picked on the menu
+                self.leaderboard = leaderboard::load(self.hardcore());
+                self.show_leaderboard = true;
+            }
+            (_, KeyCode::Char('i')) => self.show_stats = true,
+            (_, KeyCode::Char('e')) => self.state = GameState::Settings,
+            (_, KeyCode::Char('b')) => self.browse_seeds(),
+            (_, KeyCode::Char('t')) => self.cycle_theme(),
+            (_, KeyCode::Char('o')) => self.cycle_border(),
+            (_, KeyCode::Char('c')) if self.saved_game => {
+                // A save that can't be read is gone all the same, the menu stays up
+                let _ = save::load_game(self);
+                self.saved_game = false;
+            }
+            _ => {}
+        }
+    }
+
+    // Up and down pick a row, left, right and Enter change it. Leaving saves
+    fn handle_settings_key(&mut self, key_event: KeyEvent) {
+        let setting = Setting::ALL[self.settings_row];
+        match (self.keys.action(key_event.code), key_event.code) {
+            (Some(Action::Quit), _) => {
+                self.close_settings();
+                self.exit();
+            }
+            (Some(Action::Up), _) | (_, KeyCode::Char('w' | 'W')) => self.settings_row = self.settings_row.saturating_sub(1),
+            (Some(Action::Down), _) | (_, KeyCode::Char('s' | 'S')) => {
+                self.settings_row = (self.settings_row + 1).min(Setting::ALL.len() - 1)
+            }
+            (Some(Action::Left), _) | (_, KeyCode::Char('a' | 'A')) => self.change_setting(setting, false),
+            (Some(Action::Right), _) | (_, KeyCode::Char('d' | 'D') | KeyCode::Enter) => self.change_setting(setting, true),
+            (_, KeyCode::Char('e')) => self.close_settings(),
+            _ => {}
+        }
+    }
+}
+
+// Each line reads `tick N: <command>`, blank lines and `#` comments are skipped
+pub(crate) fn parse_script(source: &str) -> io::Result<VecDeque<ScriptEvent>> {
+    let mut events = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let event = parse_script_line(line).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("script line {}: cannot parse '{line}'", index + 1),
+            )
+        })?;
+        events.push(event);
+    }
+    // Stable sort keeps commands for the same tick in file order
+    events.sort_by_key(|event| event.tick);
+    Ok(events.into())
+}
+
+fn parse_script_line(line: &str) -> Option<ScriptEvent> {
+    let (tick, command) = line.strip_prefix("tick")?.split_once(':')?;
+    let command = match command.split_whitespace().collect::<Vec<_>>().as_slice() {
+        ["turn", "up"] => ScriptCommand::Turn(Direction::Up),
+        ["turn", "down"] => ScriptCommand::Turn(Direction::Down),
+        ["turn", "left"] => ScriptCommand::Turn(Direction::Left),
+        ["turn", "right"] => ScriptCommand::Turn(Direction::Right),
+        ["pause"] => ScriptCommand::Pause,
+        ["quit"] => ScriptCommand::Quit,
+        _ => return None,
+    };
+    Some(ScriptEvent {
+        tick: tick.trim().parse().ok()?,
+        command,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_script_of_turns_steers_the_head_along_its_path() {
+        let script = "# up two, left two, then down\ntick 2: turn left\ntick 4: turn down\n";
+        let head_after = |ticks: usize| {
+            let mut app = App::default();
+            app.seed = Some(1);
+            app.start_direction = Some(Direction::Up);
+            app.script = parse_script(script).unwrap();
+            app.run_headless(std::iter::empty(), ticks);
+            app.player().dot.clone()
+        };
+        let start = head_after(0);
+        let path: Vec<(i32, i32)> = (1..=6)
+            .map(|ticks| {
+                let head = head_after(ticks);
+                (i32::from(head.x) - i32::from(start.x), i32::from(head.y) - i32::from(start.y))
+            })
+            .collect();
+        assert_eq!(path, [(0, -1), (0, -2), (-1, -2), (-2, -2), (-2, -1), (-2, 0)]);
+    }
+
+    #[test]
+    fn a_script_pauses_and_refuses_what_it_doesnt_know() {
+        let events = parse_script("tick 3: pause\ntick 1: turn up\n").unwrap();
+        let commands: Vec<(u64, bool)> =
+            events.iter().map(|event| (event.tick, matches!(event.command, ScriptCommand::Pause))).collect();
+        assert_eq!(commands, [(1, false), (3, true)]);
+        assert!(parse_script("tick 1: jump").is_err());
+
+        let mut app = App::default();
+        app.seed = Some(1);
+        app.script = events;
+        app.run_headless(std::iter::empty(), 4);
+        assert!(app.paused);
+    }
+}
@@ -0,0 +1,92 @@
+use std::iter::Peekable;
+use std::str::{Chars, FromStr};
+
+// Just enough JSON for the files the game writes itself: strings, whole
+// numbers, null and arrays of strings
+
+pub(crate) fn json_string(value: &str) -> String {
+    let mut quoted = String::from("\"");
+    for character in value.chars() {
+        match character {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            character if character.is_control() => quoted.push_str(&format!("\\u{:04x}", character as u32)),
+            character => quoted.push(character),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+pub(crate) fn json_strings(values: &[String]) -> String {
+    let quoted: Vec<String> = values.iter().map(|value| json_string(value)).collect();
+    format!("[{}]", quoted.join(", "))
+}
+
+pub(crate) fn parse_string(chars: &mut Peekable<Chars>) -> Option<String> {
+    expect(chars, '"')?;
+    let mut value = String::new();
+    loop {
+        match chars.next()? {
+            '"' => return Some(value),
+            '\\' => match chars.next()? {
+                'n' => value.push('\n'),
+                't' => value.push('\t'),
+                'u' => {
+                    let code: String = chars.by_ref().take(4).collect();
+                    value.push(char::from_u32(u32::from_str_radix(&code, 16).ok()?)?);
+                }
+                escaped => value.push(escaped),
+            },
+            character => value.push(character),
+        }
+    }
+}
+
+pub(crate) fn parse_strings(chars: &mut Peekable<Chars>) -> Option<Vec<String>> {
+    expect(chars, '[')?;
+    let mut values = Vec::new();
+    if peek(chars)? == ']' {
+        chars.next();
+        return Some(values);
+    }
+    loop {
+        values.push(parse_string(chars)?);
+        match next(chars)? {
+            ',' => continue,
+            ']' => return Some(values),
+            _ => return None,
+        }
+    }
+}
+
+pub(crate) fn parse_number<T: FromStr>(chars: &mut Peekable<Chars>) -> Option<T> {
+    peek(chars)?;
+    let mut digits = String::new();
+    while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+        digits.push(digit);
+    }
+    digits.parse().ok()
+}
+
+pub(crate) fn parse_null(chars: &mut Peekable<Chars>) -> Option<()> {
+    peek(chars)?;
+    let word: String = chars.by_ref().take(4).collect();
+    (word == "null").then_some(())
+}
+
+// The next non-whitespace character, left in place
+pub(crate) fn peek(chars: &mut Peekable<Chars>) -> Option<char> {
+    while chars.next_if(|character| character.is_whitespace()).is_some() {}
+    chars.peek().copied()
+}
+
+pub(crate) fn next(chars: &mut Peekable<Chars>) -> Option<char> {
+    peek(chars)?;
+    chars.next()
+}
+
+pub(crate) fn expect(chars: &mut Peekable<Chars>, expected: char) -> Option<()> {
+    (next(chars)? == expected).then_some(())
+}
@@ -0,0 +1,156 @@
+use std::cmp::Reverse;
+use std::io;
+use std::iter::Peekable;
+use std::path::PathBuf;
+use std::str::Chars;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::data_dir;
+use crate::json::{expect, json_string, next, parse_number, parse_string, peek};
+
+const MAX_ENTRIES: usize = 10;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ScoreEntry {
+    pub(crate) name: String,
+    pub(crate) score: u32,
+    // Day the score was set, YYYY-MM-DD in UTC
+    pub(crate) date: String,
+}
+
+impl ScoreEntry {
+    pub(crate) fn new(name: String, score: u32) -> Self {
+        Self { name, score, date: today() }
+    }
+}
+
+// Hardcore scores go on a board of their own, every other difficulty shares one
+fn leaderboard_path(hardcore: bool) -> Option<PathBuf> {
+    let name = if hardcore { "leaderboard_hardcore.json" } else { "leaderboard.json" };
+    data_dir().map(|dir| dir.join(name))
+}
+
+// A missing or broken file reads as an empty board rather than stopping the game
+pub(crate) fn load(hardcore: bool) -> Vec<ScoreEntry> {
+    leaderboard_path(hardcore)
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|contents| parse(&contents))
+        .unwrap_or_default()
+}
+
+// Written to a temporary file first and renamed over the old one, so a crash or
+// a second game saving at the same moment never leaves half a file behind
+fn save(entries: &[ScoreEntry], hardcore: bool) -> io::Result<()> {
+    let path = leaderboard_path(hardcore).ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no data directory"))?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let temporary = path.with_extension(format!("json.{}", std::process::id()));
+    std::fs::write(&temporary, to_json(entries))?;
+    std::fs::rename(&temporary, &path)
+}
+
+// Where `score` would land, None when it doesn't make the top MAX_ENTRIES.
+// Ties go below the scores already on the board
+pub(crate) fn rank(entries: &[ScoreEntry], score: u32) -> Option<usize> {
+    if score == 0 {
+        return None;
+    }
+    let rank = entries.iter().take_while(|entry| entry.score >= score).count();
+    (rank < MAX_ENTRIES).then_some(rank)
+}
+
+// Adds the entry to the board on disk, re-read first so scores another game saved
+// in the meantime are kept. Returns the updated board and the entry's place on it
+pub(crate) fn record(entry: ScoreEntry, hardcore: bool) -> io::Result<(Vec<ScoreEntry>, Option<usize>)> {
+    let mut entries = load(hardcore);
+    let Some(rank) = rank(&entries, entry.score) else {
+        return Ok((entries, None));
+    };
+    entries.insert(rank, entry);
+    entries.truncate(MAX_ENTRIES);
+    save(&entries, hardcore)?;
+    Ok((entries, Some(rank)))
+}
+
+fn to_json(entries: &[ScoreEntry]) -> String {
+    let rows: Vec<String> = entries
+        .iter()
+        .map(|entry| {
+            format!(
+                "  {{\"name\": {}, \"score\": {}, \"date\": {}}}",
+                json_string(&entry.name),
+                entry.score,
+                json_string(&entry.date)
+            )
+        })
+        .collect();
+    format!("[\n{}\n]\n", rows.join(",\n"))
+}
+
+// Reads back what `to_json` writes: an array of flat objects with string and
+// number values. Unknown keys are skipped, anything malformed gives None
+fn parse(source: &str) -> Option<Vec<ScoreEntry>> {
+    let mut chars = source.chars().peekable();
+    let mut entries = Vec::new();
+    expect(&mut chars, '[')?;
+    if peek(&mut chars)? == ']' {
+        chars.next();
+        return Some(entries);
+    }
+    loop {
+        entries.push(parse_entry(&mut chars)?);
+        match next(&mut chars)? {
+            ',' => continue,
+            ']' => break,
+            _ => return None,
+        }
+    }
+    entries.sort_by_key(|entry| Reverse(entry.score));
+    entries.truncate(MAX_ENTRIES);
+    Some(entries)
+}
+
+fn parse_entry(chars: &mut Peekable<Chars>) -> Option<ScoreEntry> {
+    let mut entry = ScoreEntry {
+        name: String::new(),
+        score: 0,
+        date: String::new(),
+    };
+    expect(chars, '{')?;
+    loop {
+        let key = parse_string(chars)?;
+        expect(chars, ':')?;
+        match key.as_str() {
+            "name" => entry.name = parse_string(chars)?,
+            "date" => entry.date = parse_string(chars)?,
+            "score" => entry.score = parse_number(chars)?,
+            _ if peek(chars)? == '"' => {
+                parse_string(chars)?;
+            }
+            _ => {
+                parse_number::<u32>(chars)?;
+            }
+        }
+        match next(chars)? {
+            ',' => continue,
+            '}' => return Some(entry),
+            _ => return None,
+        }
+    }
+}
+
+// Civil date from days since the epoch, after Howard Hinnant's `civil_from_days`
+fn today() -> String {
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map(|since| since.as_secs()).unwrap_or(0);
+    let days = (seconds / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
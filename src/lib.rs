@@ -0,0 +1,21 @@
+mod config;
+mod game;
+mod ghost;
+mod history;
+mod input;
+mod json;
+mod leaderboard;
+mod multi_grid;
+mod render;
+mod replay;
+mod save;
+mod shutdown;
+mod stats;
+mod versus;
+
+pub use config::{configure, Mode};
+pub use game::{App, Direction, Dot, GameEnd, GameEvent, GameOutcome};
+pub use multi_grid::MultiGrid;
+pub use replay::Replay;
+pub use shutdown::on_shutdown;
+pub use versus::Versus;
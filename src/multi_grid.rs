@@ -0,0 +1,153 @@
+use std::io;
+use std::time::{Duration, Instant};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use ratatui::{
+    style::{Modifier, Style},
+    DefaultTerminal, Frame,
+};
+
+use crate::game::{App, GameState, FRAME_INTERVAL};
+use crate::render::{board_areas, draw_frame};
+
+// Two independent boards side by side, keys go to the focused one and Tab switches focus
+#[derive(Debug)]
+pub struct MultiGrid {
+    boards: [App; 2],
+    focused: usize,
+    exit: bool,
+}
+
+impl MultiGrid {
+    pub fn new(app: App) -> Self {
+        Self {
+            boards: [app.clone(), app],
+            focused: 0,
+            exit: false,
+        }
+    }
+
+    pub fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        for board in &mut self.boards {
+            if board.state != GameState::Menu {
+                board.start_game();
+            }
+        }
+        while !self.exit {
+            let frame_start = Instant::now();
+            let (area, _) = draw_frame(terminal, |frame| self.draw(frame), false)?;
+            let frame_time = frame_start.elapsed();
+            // Input is waited for only until the sooner board's next tick, so a
+            // fast board doesn't sit on a key for a whole frame
+            let timeout = self
+                .boards
+                .iter()
+                .map(App::until_next_tick)
+                .min()
+                .unwrap_or(FRAME_INTERVAL)
+                .min(FRAME_INTERVAL);
+            self.handle_events(timeout)?;
+            for (board, area) in self.boards.iter_mut().zip(board_areas(area).iter()) {
+                board.set_too_small(!board.fits(*area));
+                board.record_frame_time(frame_time);
+                board.shake_frames = board.shake_frames.saturating_sub(1);
+                if board.is_running() {
+                    board.update()?;
+                }
+                self.exit |= board.exit;
+            }
+        }
+        Ok(())
+    }
+
+    // Each board gets half the width
+    pub fn minimum_terminal_size(&self) -> (u16, u16) {
+        let (width, height) = self.boards[0].minimum_terminal_size();
+        (width * 2, height)
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let areas = board_areas(frame.area());
+        for (index, (board, area)) in self.boards.iter().zip(areas.iter()).enumerate() {
+            board.draw_in(frame, *area);
+            if index != self.focused {
+                frame
+                    .buffer_mut()
+                    .set_style(*area, Style::default().add_modifier(Modifier::DIM));
+            }
+        }
+    }
+
+    // Takes every event already queued after the first, like a single board does
+    fn handle_events(&mut self, timeout: Duration) -> io::Result<()> {
+        let mut timeout = timeout;
+        while event::poll(timeout)? {
+            match event::read()? {
+                Event::Key(key_event) if key_event.kind == KeyEventKind::Press => self.handle_key(key_event),
+                Event::FocusLost => self.boards.iter_mut().for_each(App::focus_lost),
+                _ => {}
+            }
+            timeout = Duration::ZERO;
+        }
+        Ok(())
+    }
+
+    fn handle_key(&mut self, key_event: KeyEvent) {
+        if key_event.code == KeyCode::Tab {
+            self.focused = (self.focused + 1) % self.boards.len();
+        } else {
+            self.boards[self.focused].handle_key_event(key_event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::*;
+    use crate::game::Direction;
+
+    fn started() -> MultiGrid {
+        let mut app = App::default();
+        app.seed = Some(3);
+        app.start_direction = Some(Direction::Up);
+        let mut grid = MultiGrid::new(app);
+        grid.boards.iter_mut().for_each(App::start);
+        grid
+    }
+
+    #[test]
+    fn a_tick_on_one_board_leaves_the_other_alone() {
+        let mut grid = started();
+        let before = grid.boards[1].clone();
+        for _ in 0..5 {
+            grid.boards[0].tick(Some(Direction::Left));
+        }
+        let [moved, other] = &mut grid.boards;
+        assert_eq!(moved.tick, 5);
+        assert_ne!(moved.player().dot, other.player().dot);
+        assert_eq!(other.tick, before.tick);
+        assert_eq!(other.player().dot, before.player().dot);
+        assert_eq!(other.player().tail, before.player().tail);
+        let food = |app: &App| app.food.iter().map(|food| (food.x, food.y)).collect::<Vec<_>>();
+        assert_eq!(food(other), food(&before));
+        assert_eq!(other.rng.r#gen::<u64>(), before.rng.clone().r#gen::<u64>());
+    }
+
+    #[test]
+    fn keys_go_to_the_board_tab_focused() {
+        let mut grid = started();
+        grid.handle_key(KeyEvent::from(KeyCode::Left));
+        assert_eq!(grid.boards[0].player().next_turn(), Some(Direction::Left));
+        assert_eq!(grid.boards[1].player().next_turn(), None);
+
+        grid.handle_key(KeyEvent::from(KeyCode::Tab));
+        assert_eq!(grid.focused, 1);
+        grid.handle_key(KeyEvent::from(KeyCode::Right));
+        assert_eq!(grid.boards[1].player().next_turn(), Some(Direction::Right));
+        assert_eq!(grid.boards[0].player().next_turn(), Some(Direction::Left));
+
+        grid.handle_key(KeyEvent::from(KeyCode::Tab));
+        assert_eq!(grid.focused, 0);
+    }
+}
@@ -0,0 +1,1734 @@
+use std::io::{self, Write};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use crossterm::event::KeyCode;
+use crossterm::terminal::{BeginSynchronizedUpdate, EndSynchronizedUpdate};
+use crossterm::{execute, queue};
+use ratatui::{
+    buffer::Buffer,
+    layout::{self, Alignment, Constraint, Layout, Margin, Rect},
+    style::{Color, Modifier, Style, Stylize},
+    text::{Line, Span, Text},
+    widgets::{Block, Clear, Gauge, Paragraph, Widget},
+    DefaultTerminal, Frame,
+};
+
+use crate::game::{
+    App, Crash, DeathCause, Difficulty, Direction, Dot, FoodKind, GameState, Modifiers, Setting, Snake, SpeedPreset, FRAME_INTERVAL,
+};
+use crate::input::Action;
+
+// Where the score line sits on the board border, Hidden drops all HUD text
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum HudPosition {
+    Top,
+    Bottom,
+    Hidden,
+}
+
+// Smallest board, border included, a margin is allowed to squeeze the view down to
+const MIN_BOARD_WIDTH: u16 = 12;
+const MIN_BOARD_HEIGHT: u16 = 12;
+const FRAME_TIME_SAMPLES: usize = 20;
+const TONGUE_CYCLE: Duration = Duration::from_millis(1200);
+const TONGUE_VISIBLE: Duration = Duration::from_millis(250);
+// One bright and one dim half of the food's pulse
+const FOOD_PULSE: Duration = Duration::from_millis(500);
+// How long the points for a piece of food float above it
+const POINTS_FLOAT: Duration = Duration::from_millis(700);
+// How long a death penalty stays on the title
+const PENALTY_SHOWN: Duration = Duration::from_millis(1500);
+// Food closer to the head than this many steps is easy enough to spot without `--assist`
+const ASSIST_DISTANCE: i32 = 12;
+
+impl App {
+    pub(crate) fn draw(&self, frame: &mut Frame) {
+        self.draw_in(frame, frame.area());
+    }
+
+    pub(crate) fn record_frame_time(&mut self, frame_time: Duration) {
+        if self.frame_times.len() == FRAME_TIME_SAMPLES {
+            self.frame_times.pop_front();
+            self.frame_stamps.pop_front();
+        }
+        self.frame_times.push_back(frame_time);
+        self.frame_stamps.push_back(Instant::now());
+    }
+
+    // Cells the frame just drawn differed from the one before in, which is
+    // all ratatui sends. Only counted with the debug overlay up, a resize
+    // starts the count over
+    pub(crate) fn count_redrawn(&mut self, drawn: Option<Buffer>) {
+        if let (Some(last), Some(drawn)) = (&self.last_frame, &drawn)
+            && last.area == drawn.area
+        {
+            if self.redrawn_cells.len() == FRAME_TIME_SAMPLES {
+                self.redrawn_cells.pop_front();
+            }
+            self.redrawn_cells.push_back(last.diff(drawn).len());
+        }
+        if drawn.is_none() {
+            self.redrawn_cells.clear();
+        }
+        self.last_frame = drawn;
+    }
+
+    // Frames drawn a second over the last FRAME_TIME_SAMPLES
+    fn frames_per_second(&self) -> Option<f64> {
+        let (first, last) = (self.frame_stamps.front()?, self.frame_stamps.back()?);
+        let span = last.duration_since(*first).as_secs_f64();
+        (span > 0.0).then(|| (self.frame_stamps.len() - 1) as f64 / span)
+    }
+
+    // Top left inside the board, under the popups since those are drawn after it
+    fn render_debug_overlay(&self, board: Rect, buf: &mut Buffer) {
+        let head = &self.player().dot;
+        let food: Vec<String> = self.food.iter().take(3).map(|food| format!("{},{}", food.x, food.y)).collect();
+        let fps = self.frames_per_second().map_or_else(|| "-".to_string(), |fps| format!("{fps:.0}"));
+        // The most any recent frame redrew, the frames between ticks redraw next to nothing
+        let cells = self.redrawn_cells.iter().max().map_or_else(|| "-".to_string(), |cells| format!("{cells} peak"));
+        let row = |label: &'static str, value: String| Line::from(vec![label.dark_gray(), value.into()]);
+        let lines = vec![
+            row("head ", format!("{},{}", head.x, head.y)),
+            row("food ", food.join(" ")),
+            row("tail ", self.player().tail_length.to_string()),
+            row("tick ", format!("{}ms", self.tick_ms)),
+            row("fps  ", fps),
+            row("draw ", cells),
+        ];
+        let width = lines.iter().map(Line::width).max().unwrap_or(0) as u16 + 2;
+        let area = Rect {
+            width: width.min(board.width),
+            height: (lines.len() as u16 + 2).min(board.height),
+            ..board
+        };
+        Clear.render(area, buf);
+        Paragraph::new(Text::from(lines))
+            .block(Block::bordered().border_style(Style::default().fg(Color::DarkGray)))
+            .render(area, buf);
+    }
+
+    // Drawing lags when the average frame takes longer to draw than the steady
+    // FRAME_INTERVAL between frames allows. Ticks don't wait for frames, so a
+    // short tick alone is no sign of it
+    fn rendering_lags(&self) -> bool {
+        if self.frame_times.len() < FRAME_TIME_SAMPLES {
+            return false;
+        }
+        let average = self.frame_times.iter().sum::<Duration>() / FRAME_TIME_SAMPLES as u32;
+        average > FRAME_INTERVAL
+    }
+
+    pub(crate) fn draw_in(&self, frame: &mut Frame, area: Rect) {
+        // The demo plays on behind the menu, greyed out so the menu stays readable
+        if self.demo && self.fits(area) {
+            let board_area = self.board_area(area);
+            frame.render_widget(self, board_area);
+            dim(frame, board_area);
+            self.render_menu(frame, area);
+            return;
+        }
+        if let GameState::Splash { .. } = self.state {
+            self.render_splash(frame, area);
+            return;
+        }
+        if self.state == GameState::Menu {
+            if self.show_leaderboard {
+                self.render_leaderboard(frame, area);
+            } else if self.show_stats {
+                self.render_stats(frame, area);
+            } else {
+                self.render_menu(frame, area);
+            }
+            return;
+        }
+        if self.state == GameState::Settings {
+            self.render_settings(frame, area);
+            return;
+        }
+
+        if !self.fits(area) {
+            self.render_too_small(frame, area);
+            return;
+        }
+
+        let board_area = self.board_area(area);
+        frame.render_widget(self, board_area);
+
+        if self.state == GameState::Browse {
+            self.render_seed_bar(frame, area, board_area);
+        } else if self.hud_position != HudPosition::Hidden && board_area.bottom() < area.bottom() {
+            let bar_area = Rect {
+                y: board_area.bottom(),
+                height: 1,
+                ..board_area
+            };
+            self.render_length_bar(frame, bar_area);
+        }
+
+        if self.radar {
+            self.render_radar(frame, area);
+        }
+
+        if self.heat_map && matches!(self.state, GameState::GameOver | GameState::Won) {
+            self.render_heat_map(frame, board_area);
+        }
+        
+        if self.flashing() {
+            match &self.crash {
+                Some(crash) if self.last_breath() => highlight_crash(frame, board_area, &self.view(board_area), crash),
+                _ => frame.buffer_mut().set_style(board_area, Style::default().bg(Color::Red)),
+            }
+            return;
+        }
+
+        match self.state {
+            _ if self.connection_lost => self.render_connection_lost(frame, area),
+            _ if self.confirm_quit => self.render_quit_prompt(frame, area),
+            GameState::GameOver | GameState::Won if self.name_entry.is_some() => self.render_name_entry(frame, area),
+            GameState::GameOver | GameState::Won if self.show_leaderboard => self.render_leaderboard(frame, area),
+            GameState::GameOver => self.render_game_over_popup(frame, area),
+            GameState::Won => self.render_win_popup(frame, area),
+            GameState::Countdown { until } => self.render_countdown(frame, area, until),
+            GameState::Playing if self.paused => self.render_pause_popup(frame, area),
+            _ => {}
+        }
+    }
+
+    // The controls as they are bound right now, config remaps included
+    fn render_splash(&self, frame: &mut Frame, area: Rect) {
+        let splash_area = centered_rect(50, 45, area);
+        frame.render_widget(Clear, splash_area);
+
+        let bound = |action: Action| {
+            let names: Vec<String> = self.keys.keys(action).map(key_name).collect();
+            format!("<{}>", names.join("/"))
+        };
+        let row = |label: &'static str, keys: String| Line::from(vec![label.into(), keys.blue().bold()]);
+        let moves = if self.relative_controls {
+            format!("{} {} <,/.>", bound(Action::Left), bound(Action::Right))
+        } else {
+            [Action::Up, Action::Down, Action::Left, Action::Right].map(bound).join(" ")
+        };
+        let lines = vec![
+            Line::from(""),
+            Line::from("Snake".bold().yellow()),
+            Line::from(""),
+            row(if self.relative_controls { "Turn left/right " } else { "Move " }, moves),
+            row(if self.players == 2 { "Player two " } else { "Also " }, "<WASD>".to_string()),
+            row("Pause ", bound(Action::Pause)),
+            row("Quit ", bound(Action::Quit)),
+            row("Restart after a run ", bound(Action::Restart)),
+            row("Back ", "<Esc>".to_string()),
+            Line::from(vec![
+                "Boost ".into(),
+                "<b>".blue().bold(),
+                " - Speed ".into(),
+                "<+/->".blue().bold(),
+                " - Mute ".into(),
+                "<m>".blue().bold(),
+            ]),
+            Line::from(vec![
+                "Colors ".into(),
+                "<t>".blue().bold(),
+                " - Border ".into(),
+                "<o>".blue().bold(),
+            ]),
+            Line::from(""),
+            Line::from(vec!["Press ".into(), "<Enter>".blue().bold(), " to start".into()]),
+        ];
+
+        let block = Block::bordered()
+            .title(" Controls ".bold())
+            .border_set(self.theme.popup_border.set())
+            .border_style(self.theme.border_style());
+        let paragraph = Paragraph::new(Text::from(lines))
+            .block(block)
+            .alignment(Alignment::Center);
+        frame.render_widget(paragraph, splash_area);
+    }
+
+    // One row per difficulty, the full width inside the menu border. Empty when
+    // the menu isn't what's on screen
+    pub(crate) fn menu_item_areas(&self, area: Rect) -> Vec<(Difficulty, Rect)> {
+        if self.state != GameState::Menu || self.show_leaderboard || self.show_stats {
+            return Vec::new();
+        }
+        // Past the border, the blank line, the title and the blank line after it
+        let inner = centered_rect(40, 40, area).inner(Margin::new(1, 1));
+        Difficulty::ALL
+            .into_iter()
+            .enumerate()
+            .map(|(index, difficulty)| {
+                let row = Rect {
+                    y: inner.y + 3 + index as u16,
+                    height: 1,
+                    ..inner
+                };
+                (difficulty, row.intersection(inner))
+            })
+            .collect()
+    }
+
+    fn render_menu(&self, frame: &mut Frame, area: Rect) {
+        let menu_area = centered_rect(40, 40, area);
+        frame.render_widget(Clear, menu_area);
+
+        let mut menu_text = vec![
+            Line::from(""),
+            Line::from("Snake".bold().yellow()),
+            Line::from(""),
+        ];
+        for difficulty in Difficulty::ALL {
+            let label = format!(" {} ", difficulty.label());
+            menu_text.push(if difficulty == self.difficulty {
+                Line::from(label.black().on_yellow().bold())
+            } else {
+                Line::from(label)
+            });
+        }
+        menu_text.push(Line::from(""));
+        let mut speeds = Line::from("Speed");
+        for preset in SpeedPreset::ALL {
+            let label = format!(" {} ", preset.label());
+            speeds.push_span(if Some(preset) == self.speed_preset() { label.black().on_yellow().bold() } else { label.into() });
+        }
+        menu_text.push(speeds);
+        menu_text.push(Line::from(vec![
+            "Choose ".into(),
+            "<Up/Down>".blue().bold(),
+            " - Speed ".into(),
+            "<1-4>".blue().bold(),
+            " - Start ".into(),
+            "<Enter>".blue().bold(),
+        ]));
+        menu_text.push(Line::from(vec![
+            "Top scores ".into(),
+            "<L>".blue().bold(),
+            " - Stats ".into(),
+            "<I>".blue().bold(),
+            " - Settings ".into(),
+            "<E>".blue().bold(),
+        ]));
+        let mut more = Line::from(vec!["Pick a board ".into(), "<B>".blue().bold()]);
+        if self.saved_game {
+            more.extend([" - Continue ".into(), "<C>".blue().bold()]);
+        }
+        menu_text.push(more);
+
+        let menu_block = Block::bordered()
+            .title(" Difficulty ".bold())
+            .border_set(self.theme.popup_border.set())
+            .border_style(self.theme.border_style());
+
+        let menu_paragraph = Paragraph::new(Text::from(menu_text))
+            .block(menu_block)
+            .alignment(Alignment::Center);
+
+        frame.render_widget(menu_paragraph, menu_area);
+    }
+
+    fn render_name_entry(&self, frame: &mut Frame, area: Rect) {
+        let popup_area = centered_rect(40, 30, area);
+        frame.render_widget(Clear, popup_area);
+
+        let name = self.name_entry.as_deref().unwrap_or_default();
+        let lines = vec![
+            Line::from(""),
+            Line::from("New top score!".bold().yellow()),
+            Line::from(""),
+            Line::from(vec!["Your score: ".bold(), self.score().to_string().blue().bold()]),
+            Line::from(vec!["Name: ".bold(), name.to_string().yellow().bold(), "_".slow_blink()]),
+            Line::from(""),
+            Line::from(vec!["Save ".into(), "<Enter>".blue().bold()]),
+        ];
+
+        let block = Block::bordered()
+            .title(" Leaderboard ".bold())
+            .border_set(self.theme.popup_border.set())
+            .border_style(self.theme.border_style())
+            .style(Style::default().bg(Color::DarkGray));
+        let paragraph = Paragraph::new(Text::from(lines))
+            .block(block)
+            .alignment(Alignment::Center);
+        frame.render_widget(paragraph, popup_area);
+    }
+
+    // The top scores, with the run that just got onto the board highlighted
+    fn render_leaderboard(&self, frame: &mut Frame, area: Rect) {
+        let popup_area = centered_rect(40, 40, area);
+        frame.render_widget(Clear, popup_area);
+
+        let mut lines = vec![Line::from("")];
+        if self.leaderboard.is_empty() {
+            lines.push(Line::from("No scores yet"));
+        }
+        for (index, entry) in self.leaderboard.iter().enumerate() {
+            let row = format!("{:>2}. {:<12} {:>5}  {}", index + 1, entry.name, entry.score, entry.date);
+            lines.push(if self.leaderboard_rank == Some(index) {
+                Line::from(row.black().on_yellow().bold())
+            } else {
+                Line::from(row)
+            });
+        }
+        lines.push(Line::from(""));
+        lines.push(Line::from(vec!["Back ".into(), "<Enter>".blue().bold()]));
+
+        let block = Block::bordered()
+            .title(if self.hardcore() { " Hardcore top scores " } else { " Top scores " }.bold())
+            .border_set(self.theme.popup_border.set())
+            .border_style(self.theme.border_style())
+            .style(Style::default().bg(Color::DarkGray));
+        let paragraph = Paragraph::new(Text::from(lines))
+            .block(block)
+            .alignment(Alignment::Center);
+        frame.render_widget(paragraph, popup_area);
+    }
+
+    fn render_stats(&self, frame: &mut Frame, area: Rect) {
+        let popup_area = centered_rect(40, 40, area);
+        frame.render_widget(Clear, popup_area);
+
+        let stat = |label: &'static str, value: String| Line::from(vec![label.bold(), value.blue().bold()]);
+        let lines = vec![
+            Line::from(""),
+            stat("Games played: ", self.stats.games_played.to_string()),
+            stat("Average score: ", format!("{:.1}", self.stats.average_score())),
+            stat("Best score: ", self.stats.best_score.to_string()),
+            stat("Longest snake: ", self.stats.longest_snake.to_string()),
+            stat("Total score: ", self.stats.total_score.to_string()),
+            Line::from(""),
+            Line::from(vec!["Back ".into(), "<Enter>".blue().bold()]),
+        ];
+
+        let block = Block::bordered()
+            .title(" Stats ".bold())
+            .border_set(self.theme.popup_border.set())
+            .border_style(self.theme.border_style())
+            .style(Style::default().bg(Color::DarkGray));
+        let paragraph = Paragraph::new(Text::from(lines))
+            .block(block)
+            .alignment(Alignment::Center);
+        frame.render_widget(paragraph, popup_area);
+    }
+
+    // One row per setting, the picked one highlighted like the menu's difficulty
+    fn render_settings(&self, frame: &mut Frame, area: Rect) {
+        let popup_area = centered_rect(40, 60, area);
+        frame.render_widget(Clear, popup_area);
+
+        let mut lines = vec![Line::from("")];
+        for (index, setting) in Setting::ALL.into_iter().enumerate() {
+            let row = format!(" {:<18}{:>13} ", setting.label(), self.setting_value(setting));
+            lines.push(if index == self.settings_row {
+                Line::from(row.black().on_yellow().bold())
+            } else {
+                Line::from(row)
+            });
+        }
+        lines.push(Line::from(""));
+        lines.push(Line::from(vec![
+            "Choose ".into(),
+            "<Up/Down>".blue().bold(),
+            " - Change ".into(),
+            "<Left/Right>".blue().bold(),
+        ]));
+        lines.push(Line::from(vec!["Save and back ".into(), "<Esc>".blue().bold()]));
+        lines.push(Line::from("A continued game keeps its own rules".dark_gray()));
+
+        let block = Block::bordered()
+            .title(" Settings ".bold())
+            .border_set(self.theme.popup_border.set())
+            .border_style(self.theme.border_style())
+            .style(Style::default().bg(Color::DarkGray));
+        let paragraph = Paragraph::new(Text::from(lines))
+            .block(block)
+            .alignment(Alignment::Center);
+        frame.render_widget(paragraph, popup_area);
+    }
+
+    fn margin_area(&self, area: Rect) -> Rect {
+        area.inner(Margin {
+            horizontal: self.margin.min(area.width.saturating_sub(MIN_BOARD_WIDTH) / 2),
+            vertical: self.margin.min(area.height.saturating_sub(MIN_BOARD_HEIGHT) / 2),
+        })
+    }
+
+    // The margin gives way on a small terminal, the board itself can't. The
+    // free camera only needs room for the smallest board
+    pub fn minimum_terminal_size(&self) -> (u16, u16) {
+        if self.free_camera {
+            (self.grid_width.min(MIN_BOARD_WIDTH), self.grid_height.min(MIN_BOARD_HEIGHT))
+        } else {
+            (self.grid_width, self.grid_height)
+        }
+    }
+
+    pub(crate) fn fits(&self, area: Rect) -> bool {
+        let area = self.margin_area(area);
+        let (width, height) = self.minimum_terminal_size();
+        area.width >= width && area.height >= height
+    }
+
+    // How many characters wide a cell is drawn in `area`. Square cells fall
+    // back to one character when the doubled board doesn't fit, unless the
+    // free camera can scroll it
+    fn cell_width(&self, area: Rect) -> u16 {
+        if self.square_cells && (self.free_camera || area.width >= self.board_width(2)) { 2 } else { 1 }
+    }
+
+    // The window of the board that shows inside `board_area`. It is the whole
+    // board when that fits, otherwise it keeps the head of player one in the
+    // middle until it runs into an edge of the board
+    fn view(&self, board_area: Rect) -> View {
+        let board = board_area.inner(Margin::new(1, 1));
+        let cell_width = self.cell_width(board_area);
+        let columns = self.columns().min(board.width / cell_width);
+        let rows = self.rows().min(board.height);
+        let head = &self.player().dot;
+        View {
+            board,
+            cell_width,
+            x: head.x.saturating_sub(columns / 2).min(self.columns() - columns),
+            y: head.y.saturating_sub(rows / 2).min(self.rows() - rows),
+            columns,
+            rows,
+        }
+    }
+
+    // The board with its border when each cell is `cell_width` characters wide
+    fn board_width(&self, cell_width: u16) -> u16 {
+        self.columns() * cell_width + 2
+    }
+
+    // Centered board area inside the margin, nudged sideways while a screen shake is running
+    fn board_area(&self, area: Rect) -> Rect {
+        let area = self.margin_area(area);
+        // Whole cells only, a scrolled board can't show half of one
+        let cell_width = self.cell_width(area);
+        let width = self.board_width(cell_width).min(area.width - area.width.saturating_sub(2) % cell_width);
+        let height = self.grid_height.min(area.height);
+        let mut x = area.x + (area.width - width) / 2;
+        let y = area.y + (area.height - height) / 2;
+
+        if self.shake_frames > 0 {
+            let max_x = area.right() - width;
+            x = if self.shake_frames.is_multiple_of(2) {
+                (x + 1).min(max_x)
+            } else {
+                x.saturating_sub(1).max(area.x)
+            };
+        }
+
+        Rect { x, y, width, height }
+    }
+
+    // The seed on show and the keys for the browser, under the board or over
+    // its bottom border when there is no room below it
+    fn render_seed_bar(&self, frame: &mut Frame, area: Rect, board_area: Rect) {
+        let y = if board_area.bottom() < area.bottom() { board_area.bottom() } else { board_area.bottom() - 1 };
+        let line = Line::from(vec![
+            " Seed ".into(),
+            self.game_seed.unwrap_or_default().to_string().yellow().bold(),
+            " - Another ".into(),
+            "<Left/Right>".blue().bold(),
+            " - Play ".into(),
+            "<Enter>".blue().bold(),
+            " ".into(),
+        ]);
+        frame.render_widget(line.centered(), Rect { y, height: 1, ..board_area });
+    }
+
+    // Out of the cells the snake can reach, the same count that wins the game
+    fn length_fraction(&self, reachable: usize) -> f64 {
+        (self.length() as f64 / reachable.max(1) as f64).min(1.0)
+    }
+
+    fn render_length_bar(&self, frame: &mut Frame, area: Rect) {
+        let reachable = self.reachable_cells();
+        let fraction = self.length_fraction(reachable);
+        let color = match fraction {
+            f if f >= 0.75 => Color::Green,
+            f if f >= 0.5 => Color::LightGreen,
+            f if f >= 0.25 => Color::Yellow,
+            _ => Color::Red,
+        };
+
+        let gauge = Gauge::default()
+            .gauge_style(Style::default().fg(color).bg(Color::DarkGray))
+            .ratio(fraction)
+            .label(format!("Length {} / {reachable}", self.length()));
+
+        frame.render_widget(gauge, area);
+    }
+
+    // The cell just ahead of the head while the tongue flicks out, if that cell is free
+    fn tongue_cell(&self) -> Option<(Dot, char)> {
+        let phase = self.play_started.elapsed().as_millis() % TONGUE_CYCLE.as_millis();
+        if !self.tongue || phase >= TONGUE_VISIBLE.as_millis() {
+            return None;
+        }
+
+        let max_x = self.max_x();
+        let max_y = self.max_y();
+        let head = &self.player().dot;
+        let (x, y, glyph) = match self.player().direction {
+            Direction::Up if head.y > 0 => (head.x, head.y - 1, '│'),
+            Direction::Down if head.y < max_y => (head.x, head.y + 1, '│'),
+            Direction::Left if head.x > 0 => (head.x - 1, head.y, '─'),
+            Direction::Right if head.x < max_x => (head.x + 1, head.y, '─'),
+            _ => return None,
+        };
+
+        let forward = Dot { x, y };
+        let taken = self.food_at(x, y).is_some()
+            || self.bonus_at(x, y)
+            || self.portal_at(x, y)
+            || self.obstacles.contains(&forward);
+        if taken || self.occupied(&forward) {
+            return None;
+        }
+        Some((forward, glyph))
+    }
+
+    // Shades the board background by how often the head passed through each cell
+    fn render_heat_map(&self, frame: &mut Frame, board_area: Rect) {
+        let max_visits = self.visits.iter().copied().max().unwrap_or(0);
+        if max_visits == 0 {
+            return;
+        }
+
+        let view = self.view(board_area);
+        let width = self.columns();
+        let buf = frame.buffer_mut();
+        for (index, count) in self.visits.iter().enumerate() {
+            if *count == 0 {
+                continue;
+            }
+            let Some(cell) = view.cell_area(index as u16 % width, index as u16 / width) else {
+                continue;
+            };
+            let heat = f64::from(*count) / f64::from(max_visits);
+            let color = match heat {
+                h if h > 0.75 => Color::Red,
+                h if h > 0.5 => Color::Yellow,
+                h if h > 0.25 => Color::Cyan,
+                _ => Color::Blue,
+            };
+            buf.set_style(cell, Style::default().bg(color));
+        }
+    }
+
+    // Where the line from the head through the closest food meets the edge of
+    // the view, with an arrow along it. Following the arrow from the head runs
+    // into the food on the way. The assist points at far away food, food
+    // scrolled out of view always gets the arrow
+    fn assist_marker(&self, view: &View) -> Option<(Dot, char)> {
+        let head = &self.player().dot;
+        let (food, dx, dy) = self
+            .food
+            .iter()
+            .map(|food| (food, i32::from(food.x) - i32::from(head.x), i32::from(food.y) - i32::from(head.y)))
+            .min_by_key(|(_, dx, dy)| dx.abs() + dy.abs())?;
+        let far = self.assist && dx.abs() + dy.abs() > ASSIST_DISTANCE;
+        if !far && view.cell_area(food.x, food.y).is_some() {
+            return None;
+        }
+        let (last_x, last_y) = (view.x + view.columns - 1, view.y + view.rows - 1);
+        // How many times the step to the food fits before the edge on each axis
+        let room = |from: u16, step: i32, first: u16, last: u16| match step {
+            0 => f64::INFINITY,
+            _ if step > 0 => f64::from(last.saturating_sub(from)) / f64::from(step),
+            _ => f64::from(from.saturating_sub(first)) / f64::from(-step),
+        };
+        let scale = room(head.x, dx, view.x, last_x).min(room(head.y, dy, view.y, last_y));
+        let x = (f64::from(head.x) + f64::from(dx) * scale).round() as u16;
+        let y = (f64::from(head.y) + f64::from(dy) * scale).round() as u16;
+        Some((Dot { x: x.clamp(view.x, last_x), y: y.clamp(view.y, last_y) }, bearing_arrow(dx, dy)))
+    }
+
+    // Small box in the top left corner pointing from the head towards the food
+    fn render_radar(&self, frame: &mut Frame, area: Rect) {
+        let radar_area = Rect {
+            x: area.x,
+            y: area.y,
+            width: 12.min(area.width),
+            height: 3.min(area.height),
+        };
+
+        // Points at whichever piece of food is closest
+        let Some((dx, dy)) = self
+            .food
+            .iter()
+            .map(|food| (i32::from(food.x) - i32::from(self.player().dot.x), i32::from(food.y) - i32::from(self.player().dot.y)))
+            .min_by_key(|(dx, dy)| dx.abs() + dy.abs())
+        else {
+            return;
+        };
+        let distance = dx.abs() + dy.abs();
+
+        let radar = Paragraph::new(Line::from(vec![
+            bearing_arrow(dx, dy).to_string().yellow().bold(),
+            " ".into(),
+            distance.to_string().bold(),
+        ]))
+        .block(
+            Block::bordered()
+                .title(" Food ")
+                .border_set(self.theme.popup_border.set())
+                .border_style(self.theme.border_style()),
+        )
+        .alignment(Alignment::Center);
+
+        frame.render_widget(Clear, radar_area);
+        frame.render_widget(radar, radar_area);
+    }
+
+    fn render_game_over_popup(&self, frame: &mut Frame, area: Rect) {
+        dim(frame, area);
+
+        // Calculate popup size and position (centered)
+        let popup_area = centered_rect(40, 30, area);
+        
+        // Clear the area behind the popup
+        frame.render_widget(Clear, popup_area);
+        
+        let mut popup_text = vec![
+            Line::from(""),
+            Line::from(if self.death_cause == Some(DeathCause::TimeUp) { "Time's up!" } else { "Game over!" }.bold().yellow()),
+        ];
+        if let Some(reason) = self.death_reason() {
+            popup_text.push(Line::from(reason));
+        }
+        popup_text.push(Line::from(""));
+        popup_text.extend(self.result_lines());
+        
+        let popup_block = Block::bordered()
+            .title(" Popup ".bold())
+            .border_set(self.theme.popup_border.set())
+            .border_style(self.theme.border_style())
+            .style(Style::default().bg(Color::DarkGray));
+        
+        let popup_paragraph = Paragraph::new(Text::from(popup_text))
+            .block(popup_block)
+            .alignment(Alignment::Center);
+        
+        frame.render_widget(popup_paragraph, popup_area);
+    }
+
+    // Score lines and key hints shared by the game over and win popups
+    fn result_lines(&self) -> Vec<Line<'static>> {
+        let mut lines = if self.players == 1 {
+            vec![Line::from(vec![
+                "Your score: ".bold(),
+                self.player().score.to_string().blue().bold(),
+            ])]
+        } else {
+            // Whoever is still alive won, both crashing on the same tick is a
+            // draw. When time runs out the higher score wins
+            let leader = if self.death_cause == Some(DeathCause::TimeUp) {
+                match self.snakes[0].score.cmp(&self.snakes[1].score) {
+                    std::cmp::Ordering::Greater => Some(0),
+                    std::cmp::Ordering::Less => Some(1),
+                    std::cmp::Ordering::Equal => None,
+                }
+            } else {
+                self.snakes.iter().position(|snake| snake.alive)
+            };
+            let verdict = match leader {
+                Some(winner) if self.state == GameState::GameOver && self.network_player.is_some() => {
+                    if self.network_player == Some(winner) { "You win!" } else { "You lose!" }.to_string()
+                }
+                Some(winner) if self.state == GameState::GameOver => format!("Player {} wins!", winner + 1),
+                _ if self.state == GameState::GameOver => "Draw!".to_string(),
+                _ => "Board cleared!".to_string(),
+            };
+            let mut lines = vec![Line::from(verdict.green().bold())];
+            for (index, snake) in self.snakes.iter().enumerate() {
+                lines.push(Line::from(vec![
+                    format!("Player {}: ", index + 1).bold(),
+                    snake.score.to_string().blue().bold(),
+                ]));
+            }
+            lines
+        };
+        lines.extend([
+            Line::from(vec![
+                "Time: ".bold(),
+                format_clock(self.elapsed()).blue().bold(),
+            ]),
+            Line::from(vec![
+                "Best: ".bold(),
+                self.high_score.to_string().blue().bold(),
+            ]),
+        ]);
+        if let Some(seed) = self.game_seed {
+            lines.push(Line::from(vec![
+                "Seed: ".bold(),
+                seed.to_string().blue().bold(),
+                // Only the local end of a networked game reads keys itself
+                match (self.seed_copied, self.network_player) {
+                    (true, _) => " copied".green(),
+                    (false, None) => " <C> copy".into(),
+                    (false, Some(_)) => "".into(),
+                },
+            ]));
+        }
+        if self.new_record {
+            lines.push(Line::from("New record!".green().bold()));
+        }
+        lines.push(Line::from(""));
+        // A networked game can't be restarted from one end
+        lines.push(if self.network_player.is_some() {
+            Line::from(vec!["Quit ".into(), "<Q>".blue().bold()])
+        } else {
+            let mut keys = Line::from(vec![
+                "Restart ".into(),
+                "<R>".blue().bold(),
+                " - Quit ".into(),
+                "<Q>".blue().bold(),
+            ]);
+            if self.practice && self.state == GameState::GameOver {
+                keys.spans.splice(0..0, ["Undo ".into(), "<U>".blue().bold(), " - ".into()]);
+            }
+            keys
+        });
+        lines
+    }
+
+    // What ended a lost run, naming the player who crashed first with two
+    fn death_reason(&self) -> Option<String> {
+        let what = match self.death_cause? {
+            DeathCause::Wall => "hit a wall",
+            DeathCause::SelfCollision if self.players == 1 => "ran into itself",
+            DeathCause::SelfCollision => "ran into a snake",
+            DeathCause::Starved => "starved",
+            // The header already says the time is up
+            DeathCause::TimeUp => return None,
+        };
+        let who = match self.snakes.iter().position(|snake| !snake.alive) {
+            Some(index) if self.players > 1 => format!("Player {}", index + 1),
+            _ => "The snake".to_string(),
+        };
+        Some(format!("{who} {what}"))
+    }
+
+    fn render_win_popup(&self, frame: &mut Frame, area: Rect) {
+        dim(frame, area);
+
+        // Calculate popup size and position (centered)
+        let popup_area = centered_rect(40, 30, area);
+
+        // Clear the area behind the popup
+        frame.render_widget(Clear, popup_area);
+
+        let mut popup_text = vec![
+            Line::from(""),
+            Line::from("You won!".bold().yellow()),
+            Line::from(""),
+        ];
+        popup_text.extend(self.result_lines());
+
+        let popup_block = Block::bordered()
+            .title(" Popup ".bold())
+            .border_set(self.theme.popup_border.set())
+            .border_style(self.theme.border_style())
+            .style(Style::default().bg(Color::DarkGray));
+
+        let popup_paragraph = Paragraph::new(Text::from(popup_text))
+            .block(popup_block)
+            .alignment(Alignment::Center);
+
+        frame.render_widget(popup_paragraph, popup_area);
+    }
+
+    fn render_countdown(&self, frame: &mut Frame, area: Rect, until: Instant) {
+        let popup_area = centered_rect(20, 20, area);
+
+        frame.render_widget(Clear, popup_area);
+
+        // Counts 3, 2, 1 and shows "Go!" for the last part of a second
+        let remaining = until.saturating_duration_since(Instant::now()).as_secs_f64();
+        let label = if remaining > 0.5 {
+            ((remaining - 0.5).ceil() as u32).to_string()
+        } else {
+            "Go!".to_string()
+        };
+
+        let popup_text = vec![
+            Line::from(""),
+            Line::from(label.bold().yellow()),
+        ];
+
+        let popup_block = Block::bordered()
+            .border_set(self.theme.popup_border.set())
+            .border_style(self.theme.border_style())
+            .style(Style::default().bg(Color::DarkGray));
+
+        let popup_paragraph = Paragraph::new(Text::from(popup_text))
+            .block(popup_block)
+            .alignment(Alignment::Center);
+
+        frame.render_widget(popup_paragraph, popup_area);
+    }
+
+    fn render_too_small(&self, frame: &mut Frame, area: Rect) {
+        let (width, height) = self.minimum_terminal_size();
+        let popup_text = vec![
+            Line::from("Terminal too small".bold().red()),
+            Line::from(format!("Need {width}x{height}, got {}x{}", area.width, area.height)),
+            Line::from("Enlarge it to keep playing"),
+        ];
+        let top = area.y + area.height.saturating_sub(popup_text.len() as u16) / 2;
+        let text_area = Rect {
+            y: top,
+            height: area.bottom() - top,
+            ..area
+        };
+
+        frame.render_widget(
+            Paragraph::new(Text::from(popup_text)).alignment(Alignment::Center),
+            text_area,
+        );
+    }
+
+    fn render_connection_lost(&self, frame: &mut Frame, area: Rect) {
+        let popup_area = centered_rect(30, 20, area);
+        frame.render_widget(Clear, popup_area);
+
+        let popup_text = vec![
+            Line::from(""),
+            Line::from("Connection lost".bold().yellow()),
+            Line::from(""),
+            Line::from(vec!["Quit ".into(), "<Q>".blue().bold()]),
+        ];
+        let popup_block = Block::bordered()
+            .title(" Popup ".bold())
+            .border_set(self.theme.popup_border.set())
+            .border_style(self.theme.border_style())
+            .style(Style::default().bg(Color::DarkGray));
+        let popup_paragraph = Paragraph::new(Text::from(popup_text))
+            .block(popup_block)
+            .alignment(Alignment::Center);
+        frame.render_widget(popup_paragraph, popup_area);
+    }
+
+    fn render_quit_prompt(&self, frame: &mut Frame, area: Rect) {
+        let popup_area = centered_rect(30, 20, area);
+
+        frame.render_widget(Clear, popup_area);
+
+        let popup_text = vec![
+            Line::from(""),
+            Line::from("Quit?".bold().yellow()),
+            Line::from(""),
+            Line::from(vec![
+                "<Y>".blue().bold(),
+                " quit - ".into(),
+                "<N>".blue().bold(),
+                " keep playing".into(),
+            ]),
+        ];
+
+        let popup_block = Block::bordered()
+            .title(" Popup ".bold())
+            .border_set(self.theme.popup_border.set())
+            .border_style(self.theme.border_style())
+            .style(Style::default().bg(Color::DarkGray));
+
+        let popup_paragraph = Paragraph::new(Text::from(popup_text))
+            .block(popup_block)
+            .alignment(Alignment::Center);
+
+        frame.render_widget(popup_paragraph, popup_area);
+    }
+
+    fn render_pause_popup(&self, frame: &mut Frame, area: Rect) {
+        let popup_area = centered_rect(30, 20, area);
+
+        frame.render_widget(Clear, popup_area);
+
+        let popup_text = vec![
+            Line::from(""),
+            Line::from("PAUSED".bold().yellow()),
+            Line::from(""),
+            Line::from(vec![
+                "Press ".into(),
+                "<Space>".blue().bold(),
+                " to resume".into(),
+            ])
+        ];
+
+        let popup_block = Block::bordered()
+            .title(" Popup ".bold())
+            .border_set(self.theme.popup_border.set())
+            .border_style(self.theme.border_style())
+            .style(Style::default().bg(Color::DarkGray));
+
+        let popup_paragraph = Paragraph::new(Text::from(popup_text))
+            .block(popup_block)
+            .alignment(Alignment::Center);
+
+        frame.render_widget(popup_paragraph, popup_area);
+    }
+}
+
+pub(crate) fn board_areas(area: Rect) -> Rc<[Rect]> {
+    Layout::default()
+        .direction(layout::Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(area)
+}
+
+// Cool colors while the snake is slow, warming up as the tick interval shrinks
+fn speed_color(interval: Duration) -> Color {
+    match interval.as_millis() {
+        250.. => Color::Blue,
+        180.. => Color::Cyan,
+        130.. => Color::Yellow,
+        90.. => Color::LightRed,
+        _ => Color::Red,
+    }
+}
+
+// Mixes `from` into `to` by `fraction`, colors without RGB values (the terminal
+// default, indexed colors) can't be mixed and `from` is used as is
+fn blend(from: Color, to: Color, fraction: f64) -> Color {
+    let (Some(from_rgb), Some(to_rgb)) = (rgb(from), rgb(to)) else {
+        return from;
+    };
+    let mix = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * fraction).round() as u8;
+    Color::Rgb(
+        mix(from_rgb.0, to_rgb.0),
+        mix(from_rgb.1, to_rgb.1),
+        mix(from_rgb.2, to_rgb.2),
+    )
+}
+
+// Named colors as xterm draws them
+fn rgb(color: Color) -> Option<(u8, u8, u8)> {
+    Some(match color {
+        Color::Rgb(r, g, b) => (r, g, b),
+        Color::Black => (0, 0, 0),
+        Color::Red => (205, 0, 0),
+        Color::Green => (0, 205, 0),
+        Color::Yellow => (205, 205, 0),
+        Color::Blue => (0, 0, 238),
+        Color::Magenta => (205, 0, 205),
+        Color::Cyan => (0, 205, 205),
+        Color::Gray => (229, 229, 229),
+        Color::DarkGray => (127, 127, 127),
+        Color::LightRed => (255, 0, 0),
+        Color::LightGreen => (0, 255, 0),
+        Color::LightYellow => (255, 255, 0),
+        Color::LightBlue => (92, 92, 255),
+        Color::LightMagenta => (255, 0, 255),
+        Color::LightCyan => (0, 255, 255),
+        Color::White => (255, 255, 255),
+        _ => return None,
+    })
+}
+
+// Arrow for the octant of the (dx, dy) vector, y grows downwards like the board
+fn bearing_arrow(dx: i32, dy: i32) -> char {
+    if dx == 0 && dy == 0 {
+        return '•';
+    }
+    let angle = f64::from(-dy).atan2(f64::from(dx)).to_degrees();
+    let octant = ((angle / 45.0).round() as i32).rem_euclid(8);
+    ['→', '↗', '↑', '↖', '←', '↙', '↓', '↘'][octant as usize]
+}
+
+// Empty until a `--combo` multiplier is past 1x
+fn combo_label(snake: &Snake) -> Span<'static> {
+    if snake.combo > 1 {
+        format!(" x{}", snake.combo).magenta().bold()
+    } else {
+        Span::default()
+    }
+}
+
+fn key_name(code: KeyCode) -> String {
+    match code {
+        KeyCode::Char(' ') => "Space".to_string(),
+        KeyCode::Char(character) => character.to_string(),
+        KeyCode::F(number) => format!("F{number}"),
+        other => format!("{other:?}"),
+    }
+}
+
+// The part of the board on screen. `board` is the inside of the border, which
+// shows `columns` by `rows` cells from `x`, `y` on, each `cell_width`
+// characters wide
+struct View {
+    board: Rect,
+    cell_width: u16,
+    x: u16,
+    y: u16,
+    columns: u16,
+    rows: u16,
+}
+
+impl View {
+    // Where a board cell lands on screen, None when it is out of view
+    fn cell_area(&self, x: u16, y: u16) -> Option<Rect> {
+        let column = x.checked_sub(self.x).filter(|column| *column < self.columns)?;
+        let row = y.checked_sub(self.y).filter(|row| *row < self.rows)?;
+        Some(Rect {
+            x: self.board.x + column * self.cell_width,
+            y: self.board.y + row,
+            width: self.cell_width,
+            height: 1,
+        })
+    }
+
+    // The glyph is repeated across a wide cell. Cells out of view are skipped
+    fn put(&self, buf: &mut Buffer, x: u16, y: u16, (glyph, style): (char, Style)) {
+        if let Some(area) = self.cell_area(x, y) {
+            for position in area.positions() {
+                buf[position].set_char(glyph).set_style(style);
+            }
+        }
+    }
+}
+
+// The cell a run ended on, bold white on red. A wall crash
+// lights up the bit of border the head ran into
+fn highlight_crash(frame: &mut Frame, board_area: Rect, view: &View, crash: &Crash) {
+    let Some(cell) = view.cell_area(crash.cell.x, crash.cell.y) else {
+        return;
+    };
+    let cell = match crash.wall {
+        Some(Direction::Up) => Rect { y: cell.y - 1, ..cell },
+        Some(Direction::Down) => Rect { y: cell.y + 1, ..cell },
+        Some(Direction::Left) => Rect { x: cell.x - 1, width: 1, ..cell },
+        Some(Direction::Right) => Rect { x: cell.right(), width: 1, ..cell },
+        None => cell,
+    };
+    frame.buffer_mut().set_style(cell.intersection(board_area), Style::default().fg(Color::White).bg(Color::Red).bold());
+}
+
+// Greys out everything already drawn in `area` so a popup on top stands out
+fn dim(frame: &mut Frame, area: Rect) {
+    frame.buffer_mut().set_style(area, Style::default().fg(Color::DarkGray).add_modifier(Modifier::DIM));
+}
+
+// Helper function to create a centered rectangle
+fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
+    let popup_layout = Layout::default()
+        .direction(layout::Direction::Vertical)
+        .constraints([
+            Constraint::Percentage((100 - percent_y) / 2),
+            Constraint::Percentage(percent_y),
+            Constraint::Percentage((100 - percent_y) / 2),
+        ])
+        .split(r);
+
+    Layout::default()
+        .direction(layout::Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage((100 - percent_x) / 2),
+            Constraint::Percentage(percent_x),
+            Constraint::Percentage((100 - percent_x) / 2),
+        ])
+        .split(popup_layout[1])[1]
+}
+
+fn format_clock(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    format!("{:02}:{:02}", secs / 60, secs % 60)
+}
+
+// Asks the terminal to put `text` on the system clipboard with OSC 52, which
+// also reaches the local clipboard over SSH. Terminals that don't support it
+// ignore the request
+pub(crate) fn copy_to_clipboard(text: &str) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", base64(text.as_bytes()))?;
+    stdout.flush()
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::new();
+    for chunk in bytes.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (index, byte)| bits | (*byte as u32) << (16 - 8 * index));
+        for index in 0..4 {
+            encoded.push(if index <= chunk.len() {
+                ALPHABET[(bits >> (18 - 6 * index) & 63) as usize] as char
+            } else {
+                '='
+            });
+        }
+    }
+    encoded
+}
+
+// Each frame goes out as one synchronized update, so a terminal that supports
+// it shows the frame whole instead of painting it in as it arrives over a slow
+// link. Others ignore the markers. With `keep` a copy of the frame comes back
+pub(crate) fn draw_frame(
+    terminal: &mut DefaultTerminal,
+    render: impl FnOnce(&mut Frame),
+    keep: bool,
+) -> io::Result<(Rect, Option<Buffer>)> {
+    queue!(terminal.backend_mut(), BeginSynchronizedUpdate)?;
+    let frame = terminal.draw(render)?;
+    let drawn = (frame.area, keep.then(|| frame.buffer.clone()));
+    execute!(terminal.backend_mut(), EndSynchronizedUpdate)?;
+    Ok(drawn)
+}
+
+impl Widget for &App {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+
+    let game_width = self.board_width(self.cell_width(area)).min(area.width);
+    let game_height = self.grid_height.min(area.height);
+    
+    let x = area.x + (area.width.saturating_sub(game_width)) / 2;
+    let y = area.y + (area.height.saturating_sub(game_height)) / 2;
+    
+    let game_area = Rect {
+        x,
+        y,
+        width: game_width,
+        height: game_height,
+    };
+
+    let mut title = if let Some(rival) = &self.rival_run
+        && self.players == 1
+    {
+        // Without the name, the two scores take its room
+        Line::from(vec![
+            " You: ".bold(),
+            self.player().score.to_string().yellow().bold(),
+            combo_label(self.player()),
+            "  Rival: ".bold(),
+            rival.score.to_string().yellow().bold(),
+            "  Length: ".bold(),
+            self.length().to_string().yellow().bold(),
+        ])
+    } else if self.players == 1 {
+        Line::from(vec![
+            " Snake - Score: ".bold(),
+            self.player().score.to_string().yellow().bold(),
+            combo_label(self.player()),
+            "  Length: ".bold(),
+            self.length().to_string().yellow().bold(),
+        ])
+    } else {
+        let mut title = Line::from(" Snake -".bold());
+        for (index, snake) in self.snakes.iter().enumerate() {
+            let gap = if index == 0 { " " } else { "  " };
+            title.push_span(format!("{gap}P{}: ", index + 1).bold());
+            title.push_span(snake.score.to_string().yellow().bold());
+            title.push_span(combo_label(snake));
+        }
+        title
+    };
+    // A boost shows as the halved tick in its own color, there is no room
+    // left on the title of the default board for a separate marker
+    let speed = if self.boosting() {
+        format!("{}ms", self.tick_ms / 2).magenta().bold()
+    } else {
+        format!("{}ms", self.tick_ms).yellow().bold()
+    };
+    // A time attack counts down what is left instead
+    let (clock_label, clock) = match self.time_left() {
+        Some(left) => ("  Left: ", format_clock(left)),
+        None => ("  Time: ", format_clock(self.elapsed())),
+    };
+    title.extend([
+        clock_label.bold(),
+        clock.yellow().bold(),
+        "  Speed: ".bold(),
+        speed,
+        " ".into(),
+    ]);
+    if self.zen {
+        title.push_span(" zen ".green().bold());
+    }
+    // Short-lived notices, the only part of the title a hidden HUD still shows
+    let mut toasts = Vec::new();
+    if self.lives.is_some() && self.players == 1 {
+        title.push_span(format!(" Lives: {} ", self.lives_left).bold());
+        if let Some((penalty, at)) = self.penalty_taken
+            && at.elapsed() < PENALTY_SHOWN
+        {
+            let toast = format!("-{penalty} ").red().bold();
+            title.push_span(toast.clone());
+            toasts.push(toast);
+        }
+    }
+    if self.modifiers != Modifiers::default() {
+        title.push_span(format!(" {} ", self.modifiers.labels().join(" ")).magenta().bold());
+    }
+    if self.practice {
+        title.push_span(" practice ".cyan().bold());
+    }
+    if self.rendering_lags() {
+        let toast = " rendering can't keep up ".yellow();
+        title.push_span(toast.clone());
+        toasts.push(toast);
+    }
+    if self.debug {
+        title.push_span(" Tick: ".bold());
+        title.push_span(self.tick.to_string().magenta().bold());
+        if self.single_step {
+            title.push_span(" [step <F10>] ".magenta());
+        } else {
+            title.push_span(" ");
+        }
+    }
+    
+    let instructions = Line::from(vec![
+        " Move ".into(),
+        "<Arrows/WASD/hjkl>".blue().bold(),
+        " - ".bold(),
+        " Pause ".into(),
+        "<Space>".blue().bold(),
+        " - ".bold(),
+        " Quit ".into(),
+        "<Q> ".blue().bold(),
+    ]);
+    
+    let block = Block::bordered()
+        .border_set(self.theme.border.set())
+        .border_style(self.theme.border_style())
+        .style(Style::default().bg(self.theme.background));
+    let block = match self.hud_position {
+        HudPosition::Top => block
+            .title(title.centered())
+            .title_bottom(instructions.centered()),
+        HudPosition::Bottom => block
+            .title(instructions.centered())
+            .title_bottom(title.centered()),
+        HudPosition::Hidden if toasts.is_empty() => block,
+        HudPosition::Hidden => block.title(Line::from(toasts).centered()),
+    };
+
+    // Speed colors tint the whole snake and take over from the theme
+    let (head_color, tail_color, tail_end_color) = if self.speed_colors {
+        let color = speed_color(self.tick_interval());
+        (color, color, color)
+    } else {
+        (self.theme.head_color, self.theme.tail_color, self.theme.tail_end_color)
+    };
+    let player_two = self.theme.player_two_color;
+    // Head color and the tail's two gradient ends for each snake
+    let snake_colors = [(head_color, tail_color, tail_end_color), (player_two, player_two, player_two)];
+    // Only the food cells change between the two halves, so the pulse costs a
+    // frame no more than those cells
+    let dim_half = self.play_started.elapsed().as_millis() % FOOD_PULSE.as_millis() >= FOOD_PULSE.as_millis() / 2;
+    let food_color = if self.animate_food && dim_half {
+        blend(self.theme.food_color, Color::Black, 0.45)
+    } else {
+        self.theme.food_color
+    };
+    let food_style = Style::default().fg(food_color).bold();
+    // Food that clears walls wears the wall color so it reads as theirs
+    let clear_style = Style::default().fg(self.theme.wall_color).bold();
+    let bonus_style = Style::default().fg(self.theme.bonus_color).bold();
+    let wall_style = Style::default().fg(self.theme.wall_color);
+    let portal_style = Style::default().fg(self.theme.portal_color).bold();
+    let shadow_style = Style::default().fg(Color::DarkGray);
+
+    // Cells go straight into the buffer, each layer drawn over the one before,
+    // so a frame costs one write per thing on the board rather than a string
+    // per cell
+    let view = self.view(game_area);
+    block.render(game_area, buf);
+
+    // Shadows go first so the snake and food drawn below cover them
+    if self.shadow {
+        for dot in self.snakes.iter().flat_map(|snake| snake.tail.iter().chain([&snake.dot])) {
+            view.put(buf, dot.x + 1, dot.y + 1, ('░', shadow_style));
+        }
+    }
+
+    for wall in &self.obstacles {
+        view.put(buf, wall.x, wall.y, (self.theme.wall, wall_style));
+    }
+    // A severed tail keeps the tail's glyph in the wall's color, and dims for
+    // its last few ticks
+    for (cell, until) in &self.severed {
+        let fading = until.saturating_sub(self.tick) <= 5;
+        let style = Style::default().fg(self.theme.wall_color);
+        let style = if fading { style.add_modifier(Modifier::DIM) } else { style.bold() };
+        view.put(buf, cell.x, cell.y, (self.theme.tail, style));
+    }
+
+    if let Some((a, b)) = &self.portals {
+        for portal in [a, b] {
+            view.put(buf, portal.x, portal.y, (self.theme.portal, portal_style));
+        }
+    }
+
+    // Where the best run's and the rival's snakes were on this tick, under
+    // the live one
+    let rival_style = Style::default().fg(self.theme.player_two_color).add_modifier(Modifier::DIM);
+    let runs = [(&self.best_run, shadow_style), (&self.rival_run, rival_style)];
+    for (run, style) in runs.into_iter().filter_map(|(run, style)| Some((run.as_ref()?, style))) {
+        if self.players == 1
+            && let Some(index) = (self.tick as usize).checked_sub(1)
+            && let Some(head) = run.steps.get(index)
+        {
+            let start = (index + 1).saturating_sub(head.length as usize);
+            for step in &run.steps[start..index] {
+                view.put(buf, step.x, step.y, ('·', style));
+            }
+            view.put(buf, head.x, head.y, ('◌', style));
+        }
+    }
+
+    for (snake, (head_color, tail_color, tail_end_color)) in self.snakes.iter().zip(snake_colors) {
+        let last = snake.tail.len().saturating_sub(1).max(1) as f64;
+        for (index, tail_dot) in snake.tail.iter().enumerate() {
+            let color = blend(tail_color, tail_end_color, index as f64 / last);
+            view.put(buf, tail_dot.x, tail_dot.y, (self.theme.tail, Style::default().fg(color).bold()));
+        }
+        let head_style = Style::default().fg(head_color).bold();
+        let [up, down, left, right] = self.theme.heads;
+        let head = self.theme.head.unwrap_or(match snake.direction {
+            Direction::Up => up,
+            Direction::Down => down,
+            Direction::Left => left,
+            Direction::Right => right,
+        });
+        view.put(buf, snake.dot.x, snake.dot.y, (head, head_style));
+    }
+
+    for food in &self.food {
+        let cell = match food.kind {
+            FoodKind::Plain => (self.theme.food, food_style),
+            FoodKind::Clear => ('✚', clear_style),
+            FoodKind::Poison => ('✖', Style::default().fg(Color::Magenta).bold()),
+        };
+        view.put(buf, food.x, food.y, cell);
+    }
+    if let Some(bonus) = &self.bonus {
+        view.put(buf, bonus.x, bonus.y, (self.theme.bonus, bonus_style));
+    }
+
+    // Rises a row from just above the food it was gained on, then goes
+    if let Some(gained) = &self.points_gained
+        && gained.at.elapsed() < POINTS_FLOAT
+    {
+        let rise = if gained.at.elapsed() < POINTS_FLOAT / 2 { 1 } else { 2 };
+        if let Some(y) = gained.y.checked_sub(rise)
+            && let Some(start) = view.cell_area(gained.x, y)
+        {
+            let style = Style::default().fg(self.theme.food_color).bold();
+            for (offset, glyph) in format!("+{}", gained.points).chars().enumerate() {
+                let x = start.x + offset as u16;
+                if x < view.board.right() {
+                    buf[(x, start.y)].set_char(glyph).set_style(style);
+                }
+            }
+        }
+    }
+
+    if let Some((cell, glyph)) = self.tongue_cell() {
+        view.put(buf, cell.x, cell.y, (glyph, Style::default().magenta()));
+    }
+
+    if let Some((cell, arrow)) = self.assist_marker(&view)
+        && let Some(area) = view.cell_area(cell.x, cell.y)
+        && buf[(area.x, area.y)].symbol() == " "
+    {
+        view.put(buf, cell.x, cell.y, (arrow, Style::default().fg(self.theme.food_color).add_modifier(Modifier::DIM)));
+    }
+
+    // Last so it only lands on cells nothing was drawn in
+    if self.checkerboard {
+        let board = view.board;
+        for position in board.positions() {
+            let x = (position.x - board.x) / view.cell_width + view.x;
+            let y = position.y - board.y + view.y;
+            let cell = &mut buf[position];
+            if (x + y) % 2 == 1 && cell.symbol() == " " {
+                cell.set_bg(self.theme.checker_color);
+            }
+        }
+    }
+
+    if self.debug {
+        self.render_debug_overlay(view.board, buf);
+    }
+}
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+
+    use super::*;
+    use crate::game::{Food, MIN_TICK_MS};
+
+    #[test]
+    fn rendering_only_lags_when_frames_overrun_the_frame_interval() {
+        let mut app = App::default();
+        app.tick_ms = MIN_TICK_MS;
+        for _ in 0..FRAME_TIME_SAMPLES {
+            app.record_frame_time(Duration::from_millis(5));
+        }
+        assert!(!app.rendering_lags());
+        for _ in 0..FRAME_TIME_SAMPLES {
+            app.record_frame_time(FRAME_INTERVAL * 2);
+        }
+        assert!(app.rendering_lags());
+    }
+
+    #[test]
+    fn a_margin_insets_the_area_on_every_side_and_keeps_the_board_centered() {
+        let mut app = App::default();
+        app.grid_width = 40;
+        app.grid_height = 20;
+        let area = Rect::new(0, 0, 100, 40);
+        let plain = app.board_area(area);
+        app.margin = 2;
+        assert_eq!(app.margin_area(area), Rect::new(2, 2, 96, 36));
+        assert_eq!(app.board_area(area), plain);
+        assert_eq!(plain, Rect::new(30, 10, 40, 20));
+        // Too little room left for the smallest board and the margin gives way
+        assert_eq!(app.margin_area(Rect::new(0, 0, 14, 13)), Rect::new(1, 0, 12, 13));
+    }
+
+    #[test]
+    fn the_score_lands_where_the_hud_is_placed_and_a_hidden_hud_keeps_only_toasts() {
+        let mut app = App::default();
+        app.seed = Some(1);
+        app.lives = Some(3);
+        app.start();
+        let area = Rect::new(0, 0, 80, 30);
+        let board = app.board_area(area);
+        let row = |app: &App, y: u16| {
+            let mut buf = Buffer::empty(area);
+            app.render(area, &mut buf);
+            (board.x..board.right()).map(|x| buf[(x, y)].symbol()).collect::<String>()
+        };
+        let (top, bottom) = (board.y, board.bottom() - 1);
+
+        app.hud_position = HudPosition::Top;
+        assert!(row(&app, top).contains("Score:") && row(&app, bottom).contains("Quit"));
+        app.hud_position = HudPosition::Bottom;
+        assert!(row(&app, bottom).contains("Score:") && row(&app, top).contains("Quit"));
+
+        app.hud_position = HudPosition::Hidden;
+        for y in [top, bottom] {
+            let text = row(&app, y);
+            assert!(!text.contains("Score:") && !text.contains("Lives:") && !text.contains("Quit"), "{text}");
+        }
+        // A life lost just now still flashes its penalty, and nothing else comes with it
+        app.penalty_taken = Some((5, Instant::now()));
+        let text = row(&app, top);
+        assert!(text.contains("-5") && !text.contains("Lives:"), "{text}");
+    }
+
+    #[test]
+    fn a_crash_shakes_the_board_for_a_few_frames_and_keeps_it_on_screen() {
+        let mut app = App::default();
+        app.seed = Some(1);
+        app.start_direction = Some(Direction::Up);
+        app.screen_shake = true;
+        app.start();
+        let Dot { x, y } = app.player().dot.clone();
+        app.obstacles = vec![Dot { x, y: y - 1 }];
+        let area = Rect::new(0, 0, 100, 40);
+        let still = app.board_area(area);
+        app.tick(None);
+        assert!(app.shake_frames > 0);
+
+        // The run loop takes one off per frame drawn
+        let mut frames = 0;
+        while app.shake_frames > 0 {
+            assert_ne!(app.board_area(area).x, still.x);
+            app.shake_frames -= 1;
+            frames += 1;
+        }
+        assert!(frames > 1);
+        assert_eq!(app.board_area(area), still);
+
+        // A board as wide as the terminal has nowhere to go on one side
+        let tight = Rect::new(0, 0, still.width, 40);
+        for frames in [2, 1] {
+            app.shake_frames = frames;
+            let shaken = app.board_area(tight);
+            assert!(shaken.x >= tight.x && shaken.right() <= tight.right());
+        }
+    }
+
+    #[test]
+    fn a_faster_tick_colors_the_body_warmer() {
+        let ramp = [Color::Blue, Color::Cyan, Color::Yellow, Color::LightRed, Color::Red];
+        let warmth = |ms: u64| ramp.iter().position(|color| *color == speed_color(Duration::from_millis(ms))).unwrap();
+        assert!(warmth(60) > warmth(300));
+        assert_eq!((warmth(MIN_TICK_MS), warmth(500)), (ramp.len() - 1, 0));
+        let mut last = warmth(500);
+        for ms in (MIN_TICK_MS..500).rev() {
+            assert!(warmth(ms) >= last, "{ms}ms");
+            last = warmth(ms);
+        }
+    }
+
+    #[test]
+    fn the_arrow_points_into_the_foods_octant() {
+        let octants = [
+            ((5, 0), '→'),
+            ((4, -4), '↗'),
+            ((0, -3), '↑'),
+            ((-2, -2), '↖'),
+            ((-7, 0), '←'),
+            ((-1, 1), '↙'),
+            ((0, 9), '↓'),
+            ((6, 6), '↘'),
+        ];
+        for ((dx, dy), arrow) in octants {
+            assert_eq!(bearing_arrow(dx, dy), arrow, "{dx},{dy}");
+        }
+        // Mostly sideways still rounds to the side
+        assert_eq!(bearing_arrow(10, -3), '→');
+        assert_eq!(bearing_arrow(0, 0), '•');
+    }
+
+    #[test]
+    fn the_bar_fraction_matches_the_expected_ratio_at_sample_lengths() {
+        let mut app = App::default();
+        app.grid_width = 12;
+        app.grid_height = 12;
+        // A wall down x 5 keeps the snake in the 5 columns on its left
+        app.obstacles = (0..10).map(|y| Dot { x: 5, y }).collect();
+        let mut snake = Snake::new(Dot { x: 2, y: 5 }, 0);
+        for length in [1, 10, 25, 50, 80] {
+            snake.tail = (1..length).map(|i| Dot { x: (i % 5) as u16, y: (i / 5 % 10) as u16 }).collect();
+            app.snakes = vec![snake.clone()];
+            let reachable = app.reachable_cells();
+            assert_eq!(reachable, 50);
+            let expected = (length as f64 / 50.0).min(1.0);
+            assert!((app.length_fraction(reachable) - expected).abs() < 1e-9, "{length}");
+        }
+        // Taking the wall down counts the board again rather than reusing the last count
+        app.obstacles.clear();
+        assert_eq!(app.reachable_cells(), 100);
+    }
+
+    #[test]
+    fn the_heat_map_is_hottest_where_the_head_went_most() {
+        let mut app = App::default();
+        app.grid_width = 10;
+        app.grid_height = 10;
+        app.visits = vec![0; app.playable_cells() as usize];
+        app.visits[0] = 8;
+        app.visits[1] = 1;
+        let mut terminal = Terminal::new(TestBackend::new(10, 10)).unwrap();
+        let area = Rect::new(0, 0, 10, 10);
+        let frame = terminal.draw(|frame| app.render_heat_map(frame, area)).unwrap();
+        let view = app.view(area);
+        let background = |x: u16, y: u16| {
+            let cell = view.cell_area(x, y).unwrap();
+            frame.buffer[(cell.x, cell.y)].bg
+        };
+        assert_eq!(background(0, 0), Color::Red);
+        assert_eq!(background(1, 0), Color::Blue);
+        assert_eq!(background(2, 0), Color::Reset);
+    }
+
+    #[test]
+    fn the_shadow_only_lands_on_empty_cells_below_and_right_of_the_snake() {
+        let mut app = App::default();
+        app.grid_width = 12;
+        app.grid_height = 12;
+        app.shadow = true;
+        app.animate_food = false;
+        let mut snake = Snake::new(Dot { x: 4, y: 4 }, 2);
+        snake.direction = Direction::Down;
+        snake.tail = [Dot { x: 4, y: 3 }, Dot { x: 3, y: 3 }].into();
+        app.snakes = vec![snake];
+        app.food = vec![Food { x: 5, y: 4, kind: FoodKind::Plain }];
+        let area = Rect::new(0, 0, 12, 12);
+        let mut buf = Buffer::empty(area);
+        (&app).render(area, &mut buf);
+        let view = app.view(area);
+        let glyph = |x: u16, y: u16| {
+            let cell = view.cell_area(x, y).unwrap();
+            buf[(cell.x, cell.y)].symbol().to_string()
+        };
+        // The head's shadow shows, the tail end's falls on the head and the
+        // neck's on the food, and both of those cover it
+        assert_eq!(glyph(5, 5), "░");
+        assert_eq!(glyph(4, 4), "▼");
+        assert_eq!(glyph(5, 4), app.theme.food.to_string());
+        assert_eq!(glyph(3, 4), " ");
+        assert_eq!(glyph(4, 5), " ");
+
+        app.shadow = false;
+        let mut buf = Buffer::empty(area);
+        (&app).render(area, &mut buf);
+        let cell = view.cell_area(5, 5).unwrap();
+        assert_eq!(buf[(cell.x, cell.y)].symbol(), " ");
+    }
+
+    #[test]
+    fn the_tongue_flicks_into_an_open_cell_ahead_and_nowhere_else() {
+        let mut app = App::default();
+        app.grid_width = 12;
+        app.grid_height = 12;
+        app.tongue = true;
+        app.play_started = Instant::now();
+        let facing = |app: &mut App, x: u16, y: u16, direction: Direction| {
+            let mut snake = Snake::new(Dot { x, y }, 0);
+            snake.direction = direction;
+            app.snakes = vec![snake];
+            app.tongue_cell()
+        };
+        assert_eq!(facing(&mut app, 4, 4, Direction::Right), Some((Dot { x: 5, y: 4 }, '─')));
+        assert_eq!(facing(&mut app, 4, 4, Direction::Up), Some((Dot { x: 4, y: 3 }, '│')));
+
+        app.food = vec![Food { x: 5, y: 4, kind: FoodKind::Plain }];
+        assert_eq!(facing(&mut app, 4, 4, Direction::Right), None);
+        app.food.clear();
+        app.obstacles = vec![Dot { x: 4, y: 3 }];
+        assert_eq!(facing(&mut app, 4, 4, Direction::Up), None);
+        app.obstacles.clear();
+
+        // Out of the flicker window it stays in
+        app.play_started = Instant::now() - TONGUE_VISIBLE - Duration::from_millis(50);
+        assert_eq!(facing(&mut app, 4, 4, Direction::Right), None);
+        app.play_started = Instant::now();
+
+        // Facing any edge from anywhere never puts it off the board
+        for x in 0..10 {
+            for y in 0..10 {
+                for direction in [Direction::Up, Direction::Down, Direction::Left, Direction::Right] {
+                    if let Some((cell, _)) = facing(&mut app, x, y, direction) {
+                        assert!(cell.x <= app.max_x() && cell.y <= app.max_y(), "{x},{y} {direction:?}");
+                    }
+                }
+            }
+        }
+        assert_eq!(facing(&mut app, 9, 0, Direction::Right), None);
+        assert_eq!(facing(&mut app, 9, 0, Direction::Up), None);
+        assert_eq!(facing(&mut app, 0, 9, Direction::Left), None);
+        assert_eq!(facing(&mut app, 0, 9, Direction::Down), None);
+    }
+
+    #[test]
+    fn the_head_wears_the_glyph_for_its_heading() {
+        let mut app = App::default();
+        app.grid_width = 12;
+        app.grid_height = 12;
+        app.theme.heads = ['^', 'v', '<', '>'];
+        let area = Rect::new(0, 0, 12, 12);
+        let view = app.view(area);
+        for (direction, glyph) in [(Direction::Up, "^"), (Direction::Down, "v"), (Direction::Left, "<"), (Direction::Right, ">")] {
+            let mut snake = Snake::new(Dot { x: 4, y: 4 }, 0);
+            snake.direction = direction;
+            app.snakes = vec![snake];
+            let mut buf = Buffer::empty(area);
+            (&app).render(area, &mut buf);
+            let cell = view.cell_area(4, 4).unwrap();
+            assert_eq!(buf[(cell.x, cell.y)].symbol(), glyph);
+        }
+    }
+}
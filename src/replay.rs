@@ -0,0 +1,307 @@
+use std::io;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::{
+    layout::Rect,
+    style::Stylize,
+    text::Line,
+    widgets::Paragraph,
+    DefaultTerminal, Frame,
+};
+
+use crate::config::parse_args;
+use crate::game::{App, Difficulty, Direction, GameState, FRAME_INTERVAL};
+use crate::render::draw_frame;
+use crate::json::{expect, next, parse_null, parse_number, parse_string, parse_strings, peek};
+use crate::versus::{parse_turn, turn_code};
+
+// Playback speeds in percent of the original, `+` and `-` step through them
+const SPEEDS: [u32; 6] = [25, 50, 100, 200, 400, 800];
+const NORMAL_SPEED: usize = 2;
+
+// Something that changed the course of a run: a snake's new heading, a new
+// speed, or one of the things the game does on its own clock rather than on ticks
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ReplayInput {
+    Turn { player: usize, direction: Direction },
+    Drift,
+    Shrink,
+    BonusGone,
+    // `--time-attack` ran out
+    TimeUp,
+    // `+` or `-` set a new base tick length
+    Speed { tick_ms: u64 },
+}
+
+// Happened just before the tick with this number ran
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct ReplayStep {
+    pub(crate) tick: u64,
+    pub(crate) input: ReplayInput,
+}
+
+impl ReplayStep {
+    // `12 0 U` for a turn, `40 drift`, `41 shrink`, `55 bonus`, `90 time` and
+    // `60 speed 120` for the rest
+    pub(crate) fn encode(&self) -> String {
+        match self.input {
+            ReplayInput::Turn { player, direction } => format!("{} {player} {}", self.tick, turn_code(Some(direction))),
+            ReplayInput::Drift => format!("{} drift", self.tick),
+            ReplayInput::Shrink => format!("{} shrink", self.tick),
+            ReplayInput::BonusGone => format!("{} bonus", self.tick),
+            ReplayInput::TimeUp => format!("{} time", self.tick),
+            ReplayInput::Speed { tick_ms } => format!("{} speed {tick_ms}", self.tick),
+        }
+    }
+
+    fn parse(text: &str) -> Option<Self> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let (tick, rest) = words.split_first()?;
+        let input = match rest[..] {
+            ["speed", tick_ms] => ReplayInput::Speed { tick_ms: tick_ms.parse().ok()? },
+            [player, code] => ReplayInput::Turn {
+                player: player.parse().ok()?,
+                direction: parse_turn(code).ok()??,
+            },
+            ["drift"] => ReplayInput::Drift,
+            ["shrink"] => ReplayInput::Shrink,
+            ["bonus"] => ReplayInput::BonusGone,
+            ["time"] => ReplayInput::TimeUp,
+            _ => return None,
+        };
+        Some(Self {
+            tick: tick.parse().ok()?,
+            input,
+        })
+    }
+}
+
+// The parts of a run log line a replay needs
+#[derive(Debug, Default)]
+struct LoggedRun {
+    args: Vec<String>,
+    seed: Option<u64>,
+    difficulty: String,
+    grid_width: u16,
+    grid_height: u16,
+    // Null for a run continued from a save
+    inputs: Option<Vec<String>>,
+}
+
+// Lines logged before runs carried their inputs have no `args` or `inputs`
+// and read as if they were continued from a save
+fn parse_run(line: &str) -> Option<LoggedRun> {
+    let mut chars = line.chars().peekable();
+    let mut run = LoggedRun::default();
+    expect(&mut chars, '{')?;
+    loop {
+        let key = parse_string(&mut chars)?;
+        expect(&mut chars, ':')?;
+        let chars = &mut chars;
+        let null = peek(chars)? == 'n';
+        if null {
+            parse_null(chars)?;
+        }
+        match key.as_str() {
+            _ if null => {}
+            "args" => run.args = parse_strings(chars)?,
+            "inputs" => run.inputs = Some(parse_strings(chars)?),
+            "seed" => run.seed = Some(parse_number(chars)?),
+            "difficulty" => run.difficulty = parse_string(chars)?,
+            "grid_width" => run.grid_width = parse_number(chars)?,
+            "grid_height" => run.grid_height = parse_number(chars)?,
+            _ if peek(chars)? == '"' => {
+                parse_string(chars)?;
+            }
+            _ if peek(chars)? == '[' => {
+                parse_strings(chars)?;
+            }
+            _ => {
+                parse_number::<u128>(chars)?;
+            }
+        }
+        match next(chars)? {
+            ',' => continue,
+            '}' => return Some(run),
+            _ => return None,
+        }
+    }
+}
+
+// Plays a run from the `--log` file back on screen. The game is set up again
+// from the logged options and seed, and every logged input goes in just before
+// the tick it came before the first time, so the board plays out exactly as it did
+#[derive(Debug)]
+pub struct Replay {
+    app: App,
+    inputs: Vec<ReplayStep>,
+    // The next input still to go in
+    next_input: usize,
+    // Which run in the file, counted from one
+    run: usize,
+    paused: bool,
+    speed: usize,
+    next_tick: Instant,
+}
+
+impl Replay {
+    // Run `run` in the log, the last one when None
+    pub fn load(mut app: App, path: &Path, run: Option<usize>) -> io::Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        let lines: Vec<&str> = contents.lines().filter(|line| !line.trim().is_empty()).collect();
+        let run = run.unwrap_or(lines.len());
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+        let line = run
+            .checked_sub(1)
+            .and_then(|index| lines.get(index))
+            .ok_or_else(|| invalid(format!("{} has no run {run}, it logs {}", path.display(), lines.len())))?;
+        let logged = parse_run(line).ok_or_else(|| invalid(format!("run {run} in {} doesn't parse", path.display())))?;
+        let Some(inputs) = logged.inputs else {
+            return Err(invalid(format!("run {run} was continued from a saved game, its start isn't logged")));
+        };
+        // Runs logged before every game had a seed only have one from `--seed`
+        let Some(seed) = logged.seed else {
+            return Err(invalid(format!("run {run} has no seed logged, its food can't be laid out again")));
+        };
+        let inputs = inputs
+            .iter()
+            .map(|input| ReplayStep::parse(input))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| invalid(format!("run {run} in {} has an input that doesn't parse", path.display())))?;
+        parse_args(&mut app, logged.args)?;
+        app.seed = Some(seed);
+        app.grid_width = logged.grid_width;
+        app.grid_height = logged.grid_height;
+        app.difficulty = Difficulty::ALL
+            .into_iter()
+            .find(|difficulty| difficulty.label().eq_ignore_ascii_case(&logged.difficulty))
+            .ok_or_else(|| invalid(format!("run {run} has an unknown difficulty '{}'", logged.difficulty)))?;
+        // The logged turns already include whatever the autopilot or a script did
+        app.replaying = true;
+        app.autopilot = false;
+        app.script.clear();
+        app.training = false;
+        app.log_path = None;
+        Ok(Self {
+            app,
+            inputs,
+            next_input: 0,
+            run,
+            paused: false,
+            speed: NORMAL_SPEED,
+            next_tick: Instant::now(),
+        })
+    }
+
+    pub fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        self.restart();
+        while !self.app.exit {
+            draw_frame(terminal, |frame| self.draw(frame), false)?;
+            let timeout = self.next_tick.saturating_duration_since(Instant::now()).min(FRAME_INTERVAL);
+            self.handle_events(timeout)?;
+            let interval = self.app.tick_interval() * 100 / SPEEDS[self.speed];
+            match self.app.state {
+                GameState::Countdown { .. } => {
+                    self.app.update()?;
+                    self.next_tick = Instant::now() + interval;
+                }
+                GameState::Playing if !self.paused && Instant::now() >= self.next_tick => {
+                    self.tick();
+                    // A slow frame doesn't bunch the ticks up behind it
+                    self.next_tick = (self.next_tick + interval).max(Instant::now());
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn restart(&mut self) {
+        self.app.start_game();
+        self.next_input = 0;
+    }
+
+    // The inputs logged for this tick go in first, the clock's changes can end
+    // the run before the tick does
+    fn tick(&mut self) {
+        while let Some(step) = self.inputs.get(self.next_input)
+            && step.tick <= self.app.tick
+        {
+            match step.input {
+                ReplayInput::Turn { player, direction } => {
+                    if let Some(snake) = self.app.snakes.get_mut(player) {
+                        snake.direction = direction;
+                    }
+                }
+                ReplayInput::Drift => self.app.drift_food(),
+                ReplayInput::Shrink => self.app.shrink_tails(),
+                ReplayInput::BonusGone => self.app.bonus = None,
+                ReplayInput::TimeUp => self.app.time_up(),
+                ReplayInput::Speed { tick_ms } => self.app.tick_ms = tick_ms,
+            }
+            self.next_input += 1;
+        }
+        if self.app.state == GameState::Playing {
+            self.app.advance();
+        }
+    }
+
+    // A row under the board for the controls
+    pub fn minimum_terminal_size(&self) -> (u16, u16) {
+        let (width, height) = self.app.minimum_terminal_size();
+        (width, height + 1)
+    }
+
+    // The board above a line with the replay's own controls
+    fn draw(&self, frame: &mut Frame) {
+        let area = frame.area();
+        let board = Rect {
+            height: area.height.saturating_sub(1),
+            ..area
+        };
+        self.app.draw_in(frame, board);
+        let status = Line::from(vec![
+            format!(" Run {} - tick {} - {}% ", self.run, self.app.tick, SPEEDS[self.speed]).bold(),
+            if self.paused { "paused ".yellow().bold() } else { "".into() },
+            "<Space>".blue().bold(),
+            " pause ".into(),
+            "<.>".blue().bold(),
+            " step ".into(),
+            "<+/->".blue().bold(),
+            " speed ".into(),
+            "<R>".blue().bold(),
+            " restart ".into(),
+            "<Q>".blue().bold(),
+            " quit".into(),
+        ]);
+        let status_area = Rect {
+            y: area.bottom().saturating_sub(1),
+            height: area.height.min(1),
+            ..area
+        };
+        frame.render_widget(Paragraph::new(status), status_area);
+    }
+
+    fn handle_events(&mut self, timeout: Duration) -> io::Result<()> {
+        let mut timeout = timeout;
+        while event::poll(timeout)? {
+            if let Event::Key(key_event) = event::read()?
+                && key_event.kind == KeyEventKind::Press
+            {
+                match key_event.code {
+                    KeyCode::Char('q' | 'Q') | KeyCode::Esc => self.app.exit(),
+                    KeyCode::Char(' ') => self.paused = !self.paused,
+                    KeyCode::Char('.') if self.paused && self.app.state == GameState::Playing => self.tick(),
+                    KeyCode::Char('+' | '=') => self.speed = (self.speed + 1).min(SPEEDS.len() - 1),
+                    KeyCode::Char('-') => self.speed = self.speed.saturating_sub(1),
+                    KeyCode::Char('r' | 'R') => self.restart(),
+                    KeyCode::Char('c' | 'C') => self.app.copy_seed(),
+                    _ => {}
+                }
+            }
+            timeout = Duration::ZERO;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,253 @@
+use std::collections::VecDeque;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::config::{data_dir, grid_size};
+use crate::game::{App, Difficulty, Direction, Dot, Food, FoodKind, Modifiers, Snake};
+
+fn save_path() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join("saved_game"))
+}
+
+pub(crate) fn has_saved_game() -> bool {
+    save_path().is_some_and(|path| path.exists())
+}
+
+// One `key value...` line per piece of the board, cells written as `x,y`.
+// Bonus food is left out, it would be gone by the time the game is picked up
+pub(crate) fn saved_game(app: &App) -> String {
+    // A tail poison cut off would be gone soon, it isn't kept as wall
+    let walls = app.obstacles.iter().filter(|wall| !app.severed.iter().any(|(cell, _)| cell == *wall));
+    let mut lines = vec![
+        format!("grid {} {}", app.grid_width, app.grid_height),
+        format!("difficulty {}", app.difficulty.label()),
+        format!("rules {} {} {}", app.wrap_walls || app.modifiers.wrap, app.walls_kill, app.zen),
+        format!("tick {}", app.tick),
+        format!("tick_ms {}", app.tick_ms),
+        format!("elapsed_ms {}", app.elapsed().as_millis()),
+        format!("food {}", cells(app.food.iter().map(|food| (food.x, food.y)))),
+        format!("obstacles {}", cells(walls.map(|wall| (wall.x, wall.y)))),
+    ];
+    if app.lives.is_some() {
+        lines.push(format!("lives {}", app.lives_left));
+    }
+    if app.survival {
+        let clear = app.food.iter().filter(|food| food.kind == FoodKind::Clear);
+        lines.push(format!("survival {}", cells(clear.map(|food| (food.x, food.y)))));
+    }
+    if app.food.iter().any(|food| food.kind == FoodKind::Poison) {
+        let poison = app.food.iter().filter(|food| food.kind == FoodKind::Poison);
+        lines.push(format!("poison {}", cells(poison.map(|food| (food.x, food.y)))));
+    }
+    if let Some((a, b)) = &app.portals {
+        lines.push(format!("portals {}", cells([(a.x, a.y), (b.x, b.y)].into_iter())));
+    }
+    for snake in &app.snakes {
+        lines.push(format!(
+            "snake {},{} {:?} {} {} {}",
+            snake.dot.x,
+            snake.dot.y,
+            snake.direction,
+            snake.tail_length,
+            snake.score,
+            cells(snake.tail.iter().map(|dot| (dot.x, dot.y)))
+        ));
+    }
+    lines.join("\n") + "\n"
+}
+
+pub(crate) fn save_game(contents: &str) -> io::Result<()> {
+    let path = save_path().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no data directory"))?;
+    std::fs::create_dir_all(path.parent().unwrap_or(&path))?;
+    let temporary = path.with_extension(std::process::id().to_string());
+    std::fs::write(&temporary, contents)?;
+    std::fs::rename(&temporary, &path)
+}
+
+// Puts the saved game on the board, paused, and removes the save so the same
+// run can't be continued twice
+pub(crate) fn load_game(app: &mut App) -> io::Result<()> {
+    let path = save_path().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no data directory"))?;
+    let contents = std::fs::read_to_string(&path)?;
+    std::fs::remove_file(&path)?;
+    apply_saved_game(app, &contents)
+}
+
+// Everything is read and checked before the board is touched, a broken or
+// hand-edited save leaves the app as it was. The grid has to be one the
+// options would allow and every cell has to lie inside it
+fn apply_saved_game(app: &mut App, contents: &str) -> io::Result<()> {
+    let invalid = |line: &str| io::Error::new(io::ErrorKind::InvalidData, format!("bad saved game line '{line}'"));
+
+    let mut grid = None;
+    let mut difficulty = app.difficulty;
+    let mut rules = (app.wrap_walls, app.walls_kill, app.zen);
+    let (mut tick, mut tick_ms, mut elapsed) = (0, app.tick_ms, Duration::ZERO);
+    let (mut food, mut obstacles, mut portals, mut snakes) = (Vec::new(), Vec::new(), None, Vec::new());
+    // The food that clears walls, only a survival game has the line
+    let mut clear_food = None;
+    let mut poison_food = Vec::new();
+    let mut lives_left = None;
+    for line in contents.lines() {
+        let mut words = line.split_whitespace();
+        let key = words.next().unwrap_or_default();
+        let words: Vec<&str> = words.collect();
+        let number = |index: usize| words.get(index).and_then(|word| word.parse::<u64>().ok());
+        let flag = |index: usize| words.get(index).and_then(|word| word.parse::<bool>().ok());
+        let side = |index: usize| words.get(index).and_then(|word| grid_size(word));
+        match key {
+            "grid" => grid = Some(side(0).zip(side(1)).ok_or_else(|| invalid(line))?),
+            "difficulty" => {
+                difficulty = Difficulty::ALL
+                    .into_iter()
+                    .find(|difficulty| words.first() == Some(&difficulty.label()))
+                    .ok_or_else(|| invalid(line))?;
+            }
+            "rules" => match (flag(0), flag(1), flag(2)) {
+                (Some(wrap), Some(walls_kill), Some(zen)) => rules = (wrap, walls_kill, zen),
+                _ => return Err(invalid(line)),
+            },
+            "tick" => tick = number(0).ok_or_else(|| invalid(line))?,
+            "tick_ms" => tick_ms = number(0).ok_or_else(|| invalid(line))?,
+            "elapsed_ms" => elapsed = Duration::from_millis(number(0).ok_or_else(|| invalid(line))?),
+            "food" => food = parse_cells(&words).ok_or_else(|| invalid(line))?,
+            "obstacles" => obstacles = parse_cells(&words).ok_or_else(|| invalid(line))?,
+            "lives" => {
+                let lives = number(0).and_then(|lives| u32::try_from(lives).ok()).filter(|lives| *lives >= 1);
+                lives_left = Some(lives.ok_or_else(|| invalid(line))?);
+            }
+            "poison" => poison_food = parse_cells(&words).ok_or_else(|| invalid(line))?,
+            "survival" => clear_food = Some(parse_cells(&words).ok_or_else(|| invalid(line))?),
+            "portals" => {
+                let ends = parse_cells(&words).ok_or_else(|| invalid(line))?;
+                let [a, b] = ends[..] else {
+                    return Err(invalid(line));
+                };
+                portals = Some((a, b));
+            }
+            "snake" => snakes.push(parse_snake(&words).ok_or_else(|| invalid(line))?),
+            _ => return Err(invalid(line)),
+        }
+    }
+    let Some((width, height)) = grid else {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "saved game has no grid size"));
+    };
+    if snakes.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "saved game has no snake"));
+    }
+    let (columns, rows) = (width - 2, height - 2);
+    let snake_cells = snakes
+        .iter()
+        .flat_map(|snake| std::iter::once(&snake.dot).chain(&snake.tail))
+        .map(|dot| (dot.x, dot.y));
+    let portal_cells = portals.iter().flat_map(|(a, b)| [*a, *b]);
+    let mut cells = food.iter().chain(&obstacles).copied().chain(portal_cells).chain(snake_cells);
+    if !cells.all(|(x, y)| x < columns && y < rows) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("saved game has a cell off its {width}x{height} grid"),
+        ));
+    }
+
+    let dot = |(x, y): (u16, u16)| Dot { x, y };
+    app.grid_width = width;
+    app.grid_height = height;
+    app.difficulty = difficulty;
+    (app.wrap_walls, app.walls_kill, app.zen) = rules;
+    // What chaos rolled is in the rules and the walls by now
+    app.modifiers = Modifiers::default();
+    app.tick = tick;
+    app.tick_ms = tick_ms;
+    app.survival = clear_food.is_some();
+    // A game saved without lives plays its one life out
+    app.lives_left = lives_left.unwrap_or(1);
+    let clear_food = clear_food.unwrap_or_default();
+    app.food = food
+        .into_iter()
+        .map(|(x, y)| {
+            let kind = if clear_food.contains(&(x, y)) {
+                FoodKind::Clear
+            } else if poison_food.contains(&(x, y)) {
+                FoodKind::Poison
+            } else {
+                FoodKind::Plain
+            };
+            Food { x, y, kind }
+        })
+        .collect();
+    app.obstacles = obstacles.into_iter().map(dot).collect();
+    app.severed.clear();
+    app.portals = portals.map(|(a, b)| (dot(a), dot(b)));
+    app.players = snakes.len();
+    app.snakes = snakes;
+    app.bonus = None;
+    app.resume(elapsed);
+    Ok(())
+}
+
+fn parse_snake(words: &[&str]) -> Option<Snake> {
+    let (x, y) = parse_cell(words.first()?)?;
+    let direction = match *words.get(1)? {
+        "Up" => Direction::Up,
+        "Down" => Direction::Down,
+        "Left" => Direction::Left,
+        "Right" => Direction::Right,
+        _ => return None,
+    };
+    let mut snake = Snake::new(Dot { x, y }, words.get(2)?.parse().ok()?);
+    snake.direction = direction;
+    snake.score = words.get(3)?.parse().ok()?;
+    snake.tail = parse_cells(&words[4..])?.into_iter().map(|(x, y)| Dot { x, y }).collect::<VecDeque<_>>();
+    Some(snake)
+}
+
+fn cells(cells: impl Iterator<Item = (u16, u16)>) -> String {
+    cells.map(|(x, y)| format!("{x},{y}")).collect::<Vec<_>>().join(" ")
+}
+
+fn parse_cells(words: &[&str]) -> Option<Vec<(u16, u16)>> {
+    words.iter().map(|word| parse_cell(word)).collect()
+}
+
+fn parse_cell(word: &str) -> Option<(u16, u16)> {
+    let (x, y) = word.split_once(',')?;
+    Some((x.parse().ok()?, y.parse().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAVE: &str = "grid 20 20\ndifficulty Medium\nrules false true false\ntick 7\ntick_ms 150\nelapsed_ms 1000\nfood 3,4\nobstacles \nsnake 5,5 Up 3 2 5,6 5,7 5,8\n";
+
+    #[test]
+    fn a_saved_game_is_put_back() {
+        let mut app = App::default();
+        apply_saved_game(&mut app, SAVE).unwrap();
+        assert_eq!((app.grid_width, app.grid_height), (20, 20));
+        assert_eq!(app.player().dot, Dot { x: 5, y: 5 });
+        assert_eq!(app.player().tail.len(), 3);
+        assert_eq!(app.tick, 7);
+    }
+
+    #[test]
+    fn a_grid_the_options_refuse_is_rejected() {
+        for grid in ["grid 300 300", "grid 70000 20", "grid 5 20"] {
+            let mut app = App::default();
+            let save = SAVE.replace("grid 20 20", grid);
+            assert!(apply_saved_game(&mut app, &save).is_err(), "{grid}");
+            assert_eq!((app.grid_width, app.grid_height), (App::default().grid_width, App::default().grid_height));
+        }
+    }
+
+    #[test]
+    fn cells_off_the_grid_are_rejected() {
+        for (from, to) in [("food 3,4", "food 18,4"), ("obstacles ", "obstacles 3,18"), ("5,8\n", "5,18\n"), ("snake 5,5", "snake 30,5")] {
+            let mut app = App::default();
+            let save = SAVE.replace(from, to);
+            assert!(apply_saved_game(&mut app, &save).is_err(), "{to}");
+            assert!(app.food.is_empty());
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use std::io;
+use std::mem::discriminant;
+use std::sync::Mutex;
+
+use crate::config::{save_high_score, save_settings, save_theme_index};
+use crate::save;
+
+// Something the game has to write before it goes, with what to write
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Store {
+    // A run quit mid-game, as `save::saved_game` lays it out
+    SavedGame(String),
+    HighScore(u32),
+    // The settings screen's values, in `SETTING_KEYS` order
+    Settings([String; 9]),
+    Theme(usize),
+}
+
+impl Store {
+    fn flush(&self) -> io::Result<()> {
+        match self {
+            Store::SavedGame(contents) => save::save_game(contents),
+            Store::HighScore(score) => save_high_score(*score),
+            Store::Settings(values) => save_settings(values),
+            Store::Theme(index) => save_theme_index(*index),
+        }
+    }
+
+    // The order the writes go out in, the run first since it is the one a
+    // player would miss the most
+    fn rank(&self) -> u8 {
+        match self {
+            Store::SavedGame(_) => 0,
+            Store::HighScore(_) => 1,
+            Store::Settings(_) => 2,
+            Store::Theme(_) => 3,
+        }
+    }
+}
+
+// The writes still owed, at most one of each kind
+#[derive(Debug, Default)]
+pub(crate) struct Pending {
+    stores: Vec<Store>,
+}
+
+impl Pending {
+    // A newer value replaces the one waiting, only the last is written
+    fn put(&mut self, store: Store) {
+        self.stores.retain(|waiting| discriminant(waiting) != discriminant(&store));
+        self.stores.push(store);
+    }
+
+    // Hands every waiting write to `write` once, in order, and forgets them so
+    // a second flush has nothing left to do. A write that fails doesn't stop
+    // the ones after it
+    fn flush_with(&mut self, mut write: impl FnMut(&Store) -> io::Result<()>) {
+        let mut stores = std::mem::take(&mut self.stores);
+        stores.sort_by_key(Store::rank);
+        for store in &stores {
+            let _ = write(store);
+        }
+    }
+}
+
+// Kept outside the app so the panic hook can reach it too
+static PENDING: Mutex<Pending> = Mutex::new(Pending { stores: Vec::new() });
+
+// Leaves a write for the way out instead of making it now
+pub(crate) fn defer(store: Store) {
+    PENDING.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).put(store);
+}
+
+// Writes everything the game still owes the disk, once however many times it
+// is called: from the end of `main` and from the panic hook
+pub fn on_shutdown() {
+    PENDING.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).flush_with(Store::flush);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_store_is_flushed_once_with_its_latest_value() {
+        let mut pending = Pending::default();
+        pending.put(Store::Theme(1));
+        pending.put(Store::HighScore(10));
+        pending.put(Store::SavedGame("grid 20 20\n".to_string()));
+        pending.put(Store::HighScore(25));
+        let mut written = Vec::new();
+        pending.flush_with(|store| {
+            written.push(store.clone());
+            Err(io::Error::other("disk full"))
+        });
+        assert_eq!(
+            written,
+            [Store::SavedGame("grid 20 20\n".to_string()), Store::HighScore(25), Store::Theme(1)]
+        );
+        pending.flush_with(|store| {
+            written.push(store.clone());
+            Ok(())
+        });
+        assert_eq!(written.len(), 3);
+    }
+}
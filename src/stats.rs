@@ -0,0 +1,73 @@
+use std::io;
+use std::path::PathBuf;
+
+use crate::config::data_dir;
+
+// Lifetime totals across every finished game
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Stats {
+    pub(crate) games_played: u32,
+    pub(crate) total_score: u64,
+    pub(crate) best_score: u32,
+    pub(crate) longest_snake: usize,
+}
+
+impl Stats {
+    pub(crate) fn average_score(&self) -> f64 {
+        if self.games_played == 0 {
+            return 0.0;
+        }
+        self.total_score as f64 / self.games_played as f64
+    }
+}
+
+fn stats_path() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join("stats"))
+}
+
+// No file yet is a first run, lines that don't parse are skipped so one bad
+// value doesn't wipe the rest
+pub(crate) fn load() -> Stats {
+    let mut stats = Stats::default();
+    let contents = stats_path()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .unwrap_or_default();
+    for line in contents.lines() {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let value = value.trim();
+        match key.trim() {
+            "games_played" => stats.games_played = value.parse().unwrap_or(stats.games_played),
+            "total_score" => stats.total_score = value.parse().unwrap_or(stats.total_score),
+            "best_score" => stats.best_score = value.parse().unwrap_or(stats.best_score),
+            "longest_snake" => stats.longest_snake = value.parse().unwrap_or(stats.longest_snake),
+            _ => {}
+        }
+    }
+    stats
+}
+
+// Adds a finished game to the totals on disk, re-read first so another game
+// finishing in the meantime is counted too. The new file is renamed over the old
+// one so being killed halfway through a write keeps the previous totals
+pub(crate) fn record(score: u32, length: usize) -> io::Result<Stats> {
+    let mut stats = load();
+    stats.games_played += 1;
+    stats.total_score += u64::from(score);
+    stats.best_score = stats.best_score.max(score);
+    stats.longest_snake = stats.longest_snake.max(length);
+
+    let path = stats_path().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no data directory"))?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let contents = format!(
+        "games_played = {}\ntotal_score = {}\nbest_score = {}\nlongest_snake = {}\n",
+        stats.games_played, stats.total_score, stats.best_score, stats.longest_snake
+    );
+    let temporary = path.with_extension(std::process::id().to_string());
+    std::fs::write(&temporary, contents)?;
+    std::fs::rename(&temporary, &path)?;
+    Ok(stats)
+}
@@ -0,0 +1,315 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::{Duration, Instant};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use ratatui::DefaultTerminal;
+
+use crate::config::{grid_size, MAX_FOOD};
+use crate::game::{App, Difficulty, Direction, GameState, ObstacleLayout, FRAME_INTERVAL};
+use crate::input::Action;
+use crate::render::draw_frame;
+
+// A peer that sends nothing for this long is taken to be gone
+const PEER_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Two machines playing one board. Both ends run the same simulation from the
+// same seed and settings, and every tick each sends the turn its own snake
+// makes and waits for the other's before moving, so the boards never drift apart
+#[derive(Debug)]
+pub struct Versus {
+    app: App,
+    // Which snake is played here, the host's is the first
+    me: usize,
+    writer: TcpStream,
+    reader: BufReader<TcpStream>,
+    next_tick: Instant,
+}
+
+impl Versus {
+    // Waits for one player to connect, then sends them the seed and the rules
+    // that decide where food lands and how the snakes move
+    pub fn host(mut app: App, address: &str) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        let (mut stream, _) = listener.accept()?;
+        let seed = app.seed.unwrap_or_else(rand::random);
+        writeln!(stream, "{}", greeting(&app, seed))?;
+        app.seed = Some(seed);
+        let reader = BufReader::new(stream.try_clone()?);
+        Self::start(app, 0, stream, reader)
+    }
+
+    // The host's settings replace this end's own
+    pub fn connect(mut app: App, address: &str) -> io::Result<Self> {
+        let stream = TcpStream::connect(address)?;
+        stream.set_read_timeout(Some(PEER_TIMEOUT))?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        apply_greeting(&mut app, &line)?;
+        Self::start(app, 1, stream, reader)
+    }
+
+    // Anything that steers a snake or changes the board on its own clock would
+    // play out differently on the two ends, so it stays off
+    fn start(mut app: App, me: usize, stream: TcpStream, reader: BufReader<TcpStream>) -> io::Result<Self> {
+        stream.set_read_timeout(Some(PEER_TIMEOUT))?;
+        stream.set_nodelay(true)?;
+        app.rng = StdRng::seed_from_u64(app.seed.unwrap_or_default());
+        app.players = 2;
+        app.network_player = Some(me);
+        app.autopilot = false;
+        app.script.clear();
+        app.ghost = false;
+        app.bonus_food = false;
+        app.validate_start()
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error.to_string()))?;
+        app.start_game();
+        Ok(Self {
+            app,
+            me,
+            reader,
+            writer: stream,
+            next_tick: Instant::now(),
+        })
+    }
+
+    pub fn minimum_terminal_size(&self) -> (u16, u16) {
+        self.app.minimum_terminal_size()
+    }
+
+    pub fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        while !self.app.exit {
+            draw_frame(terminal, |frame| self.app.draw(frame), false)?;
+            let timeout = self.next_tick.saturating_duration_since(Instant::now()).min(FRAME_INTERVAL);
+            self.handle_events(timeout)?;
+            let interval = Duration::from_millis(self.app.tick_ms);
+            match self.app.state {
+                GameState::Countdown { .. } => {
+                    self.app.update()?;
+                    self.next_tick = Instant::now() + interval;
+                }
+                GameState::Playing if !self.app.connection_lost && Instant::now() >= self.next_tick => {
+                    self.tick();
+                    self.next_tick += interval;
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    // Sends this end's turn for the tick, then moves both snakes once the other
+    // end's turn for the same tick is in
+    fn tick(&mut self) {
+        let tick = self.app.tick;
+        let mine = self.app.snakes[self.me].next_turn();
+        let theirs = writeln!(self.writer, "{tick} {}", turn_code(mine)).and_then(|_| self.read_turn(tick));
+        let Ok(theirs) = theirs else {
+            self.app.connection_lost = true;
+            return;
+        };
+        self.app.snakes[1 - self.me].set_next_turn(theirs);
+        self.app.advance();
+        // A versus run doesn't go on the leaderboard
+        self.app.name_entry = None;
+    }
+
+    fn read_turn(&mut self, tick: u64) -> io::Result<Option<Direction>> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "the other player left"));
+        }
+        match line.split_whitespace().collect::<Vec<_>>()[..] {
+            [their_tick, code] if their_tick.parse() == Ok(tick) => parse_turn(code),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidData, format!("out of step at tick {tick}"))),
+        }
+    }
+
+    // Only steering and quitting, pausing one end would leave the other waiting
+    fn handle_events(&mut self, timeout: Duration) -> io::Result<()> {
+        let mut timeout = timeout;
+        while event::poll(timeout)? {
+            if let Event::Key(key_event) = event::read()?
+                && key_event.kind == KeyEventKind::Press
+            {
+                let turn = match (self.app.keys.action(key_event.code), key_event.code) {
+                    (Some(Action::Quit), _) | (_, KeyCode::Esc) => {
+                        self.app.exit();
+                        None
+                    }
+                    (Some(Action::Up), _) | (_, KeyCode::Char('w' | 'W')) => Some(Direction::Up),
+                    (Some(Action::Down), _) | (_, KeyCode::Char('s' | 'S')) => Some(Direction::Down),
+                    (Some(Action::Left), _) | (_, KeyCode::Char('a' | 'A')) => Some(Direction::Left),
+                    (Some(Action::Right), _) | (_, KeyCode::Char('d' | 'D')) => Some(Direction::Right),
+                    _ => None,
+                };
+                if let Some(direction) = turn
+                    && self.app.is_running()
+                {
+                    self.app.snakes[self.me].queue_turn(direction);
+                }
+            }
+            timeout = Duration::ZERO;
+        }
+        Ok(())
+    }
+}
+
+// The first line the host sends: the seed and every rule that changes how
+// the two simulations play out
+fn greeting(app: &App, seed: u64) -> String {
+    format!(
+        "versus {seed} {} {} {} {} {} {} {:?} {} {} {} {} {} {} {} {} {}",
+        app.grid_width,
+        app.grid_height,
+        app.difficulty.label(),
+        app.food_count,
+        app.wrap_walls,
+        app.walls_kill,
+        app.obstacle_layout,
+        app.portals_enabled,
+        app.start_length.map_or("-".to_string(), |length| length.to_string()),
+        app.start_direction.map_or("-".to_string(), |direction| format!("{direction:?}")),
+        app.growth_per_food,
+        app.combo,
+        app.classic_scoring,
+        app.chaos,
+        app.survival,
+        app.poison
+    )
+}
+
+// Takes the host's settings from its greeting, refusing any this end's own
+// options would have refused
+fn apply_greeting(app: &mut App, line: &str) -> io::Result<()> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, format!("unexpected greeting '{}'", line.trim()));
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let [
+        "versus",
+        seed,
+        width,
+        height,
+        difficulty,
+        food,
+        wrap,
+        walls_kill,
+        obstacles,
+        portals,
+        start_length,
+        start_direction,
+        growth,
+        combo,
+        classic_scoring,
+        chaos,
+        survival,
+        poison,
+    ] = words[..]
+    else {
+        return Err(invalid());
+    };
+    app.seed = Some(seed.parse().map_err(|_| invalid())?);
+    app.grid_width = grid_size(width).ok_or_else(invalid)?;
+    app.grid_height = grid_size(height).ok_or_else(invalid)?;
+    app.difficulty = Difficulty::ALL
+        .into_iter()
+        .find(|known| known.label() == difficulty)
+        .ok_or_else(invalid)?;
+    app.food_count = food
+        .parse()
+        .ok()
+        .filter(|count| (1..=MAX_FOOD).contains(count))
+        .ok_or_else(invalid)?;
+    app.wrap_walls = wrap.parse().map_err(|_| invalid())?;
+    app.walls_kill = walls_kill.parse().map_err(|_| invalid())?;
+    app.obstacle_layout = match obstacles {
+        "None" => ObstacleLayout::None,
+        "Pillars" => ObstacleLayout::Pillars,
+        "Cross" => ObstacleLayout::Cross,
+        "Frame" => ObstacleLayout::Frame,
+        _ => return Err(invalid()),
+    };
+    app.portals_enabled = portals.parse().map_err(|_| invalid())?;
+    app.start_length = match start_length {
+        "-" => None,
+        length => Some(length.parse().ok().filter(|length| *length >= 1).ok_or_else(invalid)?),
+    };
+    app.start_direction = match start_direction {
+        "-" => None,
+        "Up" => Some(Direction::Up),
+        "Down" => Some(Direction::Down),
+        "Left" => Some(Direction::Left),
+        "Right" => Some(Direction::Right),
+        _ => return Err(invalid()),
+    };
+    app.growth_per_food = growth.parse().ok().filter(|growth| *growth >= 1).ok_or_else(invalid)?;
+    app.combo = combo.parse().map_err(|_| invalid())?;
+    app.classic_scoring = classic_scoring.parse().map_err(|_| invalid())?;
+    app.chaos = chaos.parse().map_err(|_| invalid())?;
+    app.survival = survival.parse().map_err(|_| invalid())?;
+    app.poison = poison.parse().map_err(|_| invalid())?;
+    Ok(())
+}
+
+pub(crate) fn turn_code(turn: Option<Direction>) -> &'static str {
+    match turn {
+        Some(Direction::Up) => "U",
+        Some(Direction::Down) => "D",
+        Some(Direction::Left) => "L",
+        Some(Direction::Right) => "R",
+        None => "-",
+    }
+}
+
+pub(crate) fn parse_turn(code: &str) -> io::Result<Option<Direction>> {
+    match code {
+        "U" => Ok(Some(Direction::Up)),
+        "D" => Ok(Some(Direction::Down)),
+        "L" => Ok(Some(Direction::Left)),
+        "R" => Ok(Some(Direction::Right)),
+        "-" => Ok(None),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidData, format!("unknown turn '{code}'"))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_greeting_carries_the_hosts_rules() {
+        let mut host = App::default();
+        host.grid_width = 30;
+        host.grid_height = 20;
+        host.food_count = 3;
+        host.obstacle_layout = ObstacleLayout::Cross;
+        host.portals_enabled = true;
+        host.start_length = Some(6);
+        host.start_direction = Some(Direction::Down);
+        host.growth_per_food = 2;
+        host.combo = true;
+        host.classic_scoring = true;
+        host.chaos = true;
+        host.survival = true;
+        host.poison = true;
+        let mut app = App::default();
+        apply_greeting(&mut app, &greeting(&host, 7)).unwrap();
+        assert_eq!(app.seed, Some(7));
+        assert_eq!((app.grid_width, app.grid_height, app.food_count), (30, 20, 3));
+        assert_eq!(app.obstacle_layout, ObstacleLayout::Cross);
+        assert!(app.portals_enabled && app.combo && app.classic_scoring && app.chaos && app.survival && app.poison);
+        assert_eq!((app.start_length, app.start_direction), (Some(6), Some(Direction::Down)));
+        assert_eq!(app.growth_per_food, 2);
+    }
+
+    #[test]
+    fn a_grid_the_options_refuse_is_refused() {
+        let mut host = App::default();
+        host.grid_width = 300;
+        assert!(apply_greeting(&mut App::default(), &greeting(&host, 7)).is_err());
+        host.grid_width = 30;
+        host.grid_height = 5;
+        assert!(apply_greeting(&mut App::default(), &greeting(&host, 7)).is_err());
+    }
+}